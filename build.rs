@@ -1,146 +1,65 @@
-#[macro_use]
-extern crate lazy_static;
+#[cfg(feature = "bundled-chromium")]
+extern crate fs2;
+#[cfg(feature = "bundled-chromium")]
 extern crate reqwest;
+extern crate serde_json;
+#[cfg(feature = "bundled-chromium")]
+extern crate sha2;
+#[cfg(feature = "bundled-chromium")]
 extern crate zip;
 
+#[cfg(feature = "bundled-chromium")]
+#[path = "build/chromium.rs"]
+mod chromium;
+#[cfg(feature = "bundled-chromium")]
+#[path = "build/download.rs"]
+mod download;
+#[path = "build/protocol.rs"]
+mod protocol;
+
 use std::env;
-use std::fs::File;
-use std::io::Write;
 use std::path::Path;
-use std::{fs, io};
-
-// TODO make overridable
-const DEFAULT_DOWNLOAD_HOST: &'static str = "https://storage.googleapis.com";
-// TODO fetch from runtime env, fallback to config
-const REVISION: &'static str = "609904";
-
-#[cfg(target_os = "linux")]
-const FILE_NAME: &'static str = "chrome-linux";
-#[cfg(target_os = "macos")]
-const FILE_NAME: &'static str = "chrome-mac";
-#[cfg(target_os = "windows")]
-const FILE_NAME: &'static str = "chrome-win";
 
-#[cfg(target_os = "linux")]
-lazy_static! {
-    static ref DOWNLOAD_URL: String = format!(
-        "{}/chromium-browser-snapshots/Linux_x64/{}/{}.zip",
-        DEFAULT_DOWNLOAD_HOST, REVISION, FILE_NAME,
-    );
-}
+const DEFAULT_REVISION: &'static str = "609904";
 
-#[cfg(target_os = "macos")]
-lazy_static! {
-    static ref DOWNLOAD_URL: String = format!(
-        "{}/chromium-browser-snapshots/Mac/{}/{}ip",
-        DEFAULT_DOWNLOAD_HOST, REVISION, FILE_NAME,
-    );
-}
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir);
 
-#[cfg(target_os = "windows")]
-lazy_static! {
-    // TODO Windows archive name changed at r591479.
-    static ref DOWNLOAD_URL: String = format!(
-        "{}/chromium-browser-snapshots/Win/{}/{}.zip",
-        DEFAULT_DOWNLOAD_HOST,
-        REVISION,
-        FILE_NAME,
+    let browser_protocol = Path::new("protocol/browser_protocol.json");
+    let js_protocol = Path::new("protocol/js_protocol.json");
+    println!("cargo:rerun-if-changed={}", browser_protocol.display());
+    println!("cargo:rerun-if-changed={}", js_protocol.display());
+    println!("cargo:rerun-if-changed=build/protocol.rs");
+    protocol::generate(
+        &[browser_protocol, js_protocol],
+        &dest_path.join("protocol.rs"),
     );
-    //static DOWNLOAD_URL: &'static str = "%s/chromium-browser-snapshots/Win_x64/%d/%s.zip");
-}
-
-fn extract_archive(file: &mut File, outpath: &Path) {
-    let mut archive = zip::ZipArchive::new(file).unwrap();
-
-    for i in 0..archive.len() {
-        let mut file = archive.by_index(i).unwrap();
-        let outfile_name = outpath.join(file.sanitized_name());
 
-        {
-            let comment = file.comment();
-            if !comment.is_empty() {
-                println!("File {} comment: {}", i, comment);
-            }
-        }
-
-        if (&*file.name()).ends_with('/') {
-            println!(
-                "File {} extracted to \"{}\"",
-                file.name(),
-                outfile_name.display()
-            );
-            fs::create_dir_all(&outfile_name).unwrap();
+    println!("cargo:rerun-if-env-changed=PUPPETEER_SKIP_CHROMIUM_DOWNLOAD");
+    println!("cargo:rerun-if-env-changed=PUPPETEER_DOWNLOAD_HOST");
+    println!("cargo:rerun-if-env-changed=PUPPETEER_CHROMIUM_REVISION");
+    println!("cargo:rerun-if-env-changed=PUPPETEER_CHROMIUM_SHA256");
+    println!("cargo:rerun-if-env-changed=PUPPETEER_SKIP_CHROMIUM_CHECKSUM");
+    println!("cargo:rerun-if-changed=build/checksums.txt");
+    println!("cargo:rerun-if-changed=build/chromium.rs");
+    println!("cargo:rerun-if-changed=build/download.rs");
+    let revision =
+        env::var("PUPPETEER_CHROMIUM_REVISION").unwrap_or_else(|_| DEFAULT_REVISION.to_string());
+
+    // The default revision of chromium at runtime
+    println!("cargo:rustc-env=CHROMIUM_REVISION={}", revision);
+
+    #[cfg(feature = "bundled-chromium")]
+    {
+        if download::is_enabled("PUPPETEER_SKIP_CHROMIUM_DOWNLOAD") {
+            println!("Not downloading chrome, PUPPETEER_SKIP_CHROMIUM_DOWNLOAD is set");
         } else {
-            println!(
-                "File {} extracted to \"{}\" ({} bytes)",
-                file.name(),
-                outfile_name.display(),
-                file.size()
-            );
-            if let Some(p) = outfile_name.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(&p).unwrap();
-                }
-            }
-            let mut outfile = fs::File::create(&outfile_name).unwrap();
-            io::copy(&mut file, &mut outfile).unwrap();
-        }
-
-        // Get and Set permissions
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-
-            if let Some(mode) = file.unix_mode() {
-                println!(
-                    "Setting permissions {} on \"{}\"",
-                    mode,
-                    outfile_name.display()
-                );
-                fs::set_permissions(&outfile_name, fs::Permissions::from_mode(mode)).unwrap();
-            }
+            let host = env::var("PUPPETEER_DOWNLOAD_HOST")
+                .unwrap_or_else(|_| chromium::DEFAULT_DOWNLOAD_HOST.to_string());
+            download::download(&dest_path, &host, &revision, revision == DEFAULT_REVISION);
         }
     }
-}
-
-fn download(dest_path: &Path) {
-    let zip_path = &dest_path.join("chrome.zip");
-    let extract_dir = &dest_path.join(FILE_NAME);
-    let target_dir = &dest_path.join("chrome");
-
-    if !target_dir.exists() {
-        println!("Downloading chrome archive");
-
-        let mut file = File::create(&zip_path).unwrap();
-        let mut result =
-            reqwest::get(DOWNLOAD_URL.as_str()).expect("Failed to downoad chrome binary");
-
-        let mut buf: Vec<u8> = vec![];
-        result
-            .copy_to(&mut buf)
-            .expect("Failed to write downloaded binary");
-        file.write_all(&buf)
-            .expect("Failed to write downloaded binary");
-        println!("Done! Archive is now at {}", &zip_path.display());
-    }
-
-    if !target_dir.exists() {
-        println!("Extracting chrome archive");
-        let mut file = File::open(&zip_path).expect("Failed to open archive for reading");
-        extract_archive(&mut file, &dest_path);
-
-        println!("Deleting archive at {} to clean up", &zip_path.display());
-        fs::remove_file(&zip_path).expect("Failed to clean up archive");
-
-        println!("Renaming result to {}", &target_dir.display());
-        fs::rename(&extract_dir, &target_dir).expect("Failed to rename extracted chrome directory");
-    }
-
-    println!("Chrome downloaded to {}", &extract_dir.display());
-}
-
-fn main() {
-    let out_dir = env::var("OUT_DIR").unwrap();
-    let dest_path = Path::new(&out_dir);
-    download(&dest_path);
+    #[cfg(not(feature = "bundled-chromium"))]
+    println!("Not downloading chrome, the bundled-chromium feature is disabled");
 }
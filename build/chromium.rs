@@ -0,0 +1,41 @@
+//! Where chromium archives are downloaded from and what they contain
+//!
+//! Shared by the build script, which downloads the bundled revision, and the
+//! crate, whose launcher and fetcher find and download revisions at runtime.
+
+pub const DEFAULT_DOWNLOAD_HOST: &'static str = "https://storage.googleapis.com";
+
+#[cfg(target_os = "linux")]
+pub const PLATFORM: &'static str = "linux";
+#[cfg(target_os = "macos")]
+pub const PLATFORM: &'static str = "mac";
+#[cfg(target_os = "windows")]
+pub const PLATFORM: &'static str = "win64";
+
+/// The directory a chromium archive extracts to
+#[cfg(target_os = "linux")]
+pub const ARCHIVE_DIR: &'static str = "chrome-linux";
+#[cfg(target_os = "macos")]
+pub const ARCHIVE_DIR: &'static str = "chrome-mac";
+#[cfg(target_os = "windows")]
+pub const ARCHIVE_DIR: &'static str = "chrome-win";
+
+/// Where the archives are found on the download host
+#[cfg(target_os = "linux")]
+pub const DOWNLOAD_PATH: &'static str = "chromium-browser-snapshots/Linux_x64";
+#[cfg(target_os = "macos")]
+pub const DOWNLOAD_PATH: &'static str = "chromium-browser-snapshots/Mac";
+// TODO Windows archive name changed at r591479.
+#[cfg(target_os = "windows")]
+pub const DOWNLOAD_PATH: &'static str = "chromium-browser-snapshots/Win_x64";
+
+/// The URL of the archive of `revision` on `host`
+pub fn download_url(host: &str, revision: &str) -> String {
+    format!(
+        "{}/{}/{}/{}.zip",
+        host.trim_end_matches('/'),
+        DOWNLOAD_PATH,
+        revision,
+        ARCHIVE_DIR
+    )
+}
@@ -0,0 +1,215 @@
+//! Downloads the bundled chromium, if the `bundled-chromium` feature is on
+
+use crate::chromium::{self, ARCHIVE_DIR, PLATFORM};
+use fs2::FileExt;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+/// The manifest of known checksums of chromium archives
+pub const CHECKSUMS: &'static str = "build/checksums.txt";
+
+/// Whether an environment variable is set to anything but an empty string,
+/// `0` or `false`
+pub fn is_enabled(name: &str) -> bool {
+    match env::var(name) {
+        Ok(value) => value != "" && value != "0" && value != "false",
+        Err(_) => false,
+    }
+}
+
+/// Reads the SHA-256 of the archive of `platform_revision`, such as
+/// `linux-609904`, from the manifest of known checksums
+fn known_checksum(platform_revision: &str) -> Option<String> {
+    let manifest = fs::read_to_string(CHECKSUMS).expect("Failed to read the checksum manifest");
+    manifest
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            match (fields.next(), fields.next()) {
+                (Some(name), Some(checksum)) if name == platform_revision => {
+                    Some(checksum.to_lowercase())
+                }
+                _ => None,
+            }
+        })
+        .next()
+}
+
+/// A lock held while downloading, so that concurrent builds sharing the
+/// output directory don't download and extract over each other
+///
+/// This is an OS lock on the lockfile, which is released when the build
+/// exits, even when it is killed, so a slow download never counts as stale.
+struct Lock {
+    file: File,
+}
+
+impl Lock {
+    fn acquire(path: PathBuf) -> Lock {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .open(&path)
+            .unwrap_or_else(|e| panic!("Failed to open lockfile {}: {}", path.display(), e));
+        if file.try_lock_exclusive().is_err() {
+            println!("Waiting for {} to be released", path.display());
+            file.lock_exclusive()
+                .unwrap_or_else(|e| panic!("Failed to lock {}: {}", path.display(), e));
+        }
+        Lock { file: file }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Downloads `url` to `zip_path` and returns the SHA-256 of the archive
+fn fetch_archive(url: &str, zip_path: &Path) -> String {
+    let mut response = reqwest::get(url).expect("Failed to download chrome binary");
+    if !response.status().is_success() {
+        panic!(
+            "Failed to download chrome binary: {} responded with {}",
+            url,
+            response.status()
+        );
+    }
+
+    let mut file = File::create(zip_path).expect("Failed to create the archive");
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = response
+            .read(&mut buffer)
+            .expect("Failed to download chrome binary");
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+        file.write_all(&buffer[..read])
+            .expect("Failed to write downloaded binary");
+    }
+
+    format!("{:x}", hasher.result())
+}
+
+fn extract_archive(file: &mut File, outpath: &Path) {
+    let mut archive = zip::ZipArchive::new(file).unwrap();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        // Refuse absolute paths and `..`, rather than rewriting them into
+        // another path within outpath
+        let outfile_name = match file.enclosed_name() {
+            Some(name) => outpath.join(name),
+            None => panic!("Archive entry {} is outside of the archive", file.name()),
+        };
+
+        if (&*file.name()).ends_with('/') {
+            fs::create_dir_all(&outfile_name).unwrap();
+        } else {
+            if let Some(p) = outfile_name.parent() {
+                if !p.exists() {
+                    fs::create_dir_all(&p).unwrap();
+                }
+            }
+            let mut outfile = fs::File::create(&outfile_name).unwrap();
+            io::copy(&mut file, &mut outfile).unwrap();
+        }
+
+        // Get and Set permissions
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Some(mode) = file.unix_mode() {
+                fs::set_permissions(&outfile_name, fs::Permissions::from_mode(mode)).unwrap();
+            }
+        }
+    }
+}
+
+/// Downloads chrome to `<dest_path>/chrome-<revision>`
+///
+/// The archive is extracted to a temporary directory, which is renamed once
+/// complete. So if the target directory exists, it holds the whole revision.
+///
+/// Archives with no known checksum fail the build, except for the default
+/// revision, whose checksums have not been vetted yet.
+pub fn download(dest_path: &Path, host: &str, revision: &str, is_default_revision: bool) {
+    let target_dir = &dest_path.join(format!("chrome-{}", revision));
+    if target_dir.exists() {
+        return;
+    }
+
+    let _lock = Lock::acquire(dest_path.join("chrome.lock"));
+    // Another build may have downloaded it while we waited for the lock
+    if target_dir.exists() {
+        return;
+    }
+
+    let zip_path = &dest_path.join(format!("chrome-{}.zip", revision));
+    let extract_dir = &dest_path.join(format!("chrome-{}.tmp", revision));
+    let url = chromium::download_url(host, revision);
+    println!("Downloading chrome archive from {}", url);
+    let checksum = fetch_archive(&url, zip_path);
+
+    let platform_revision = format!("{}-{}", PLATFORM, revision);
+    let expected_checksum = env::var("PUPPETEER_CHROMIUM_SHA256")
+        .ok()
+        .map(|checksum| checksum.to_lowercase())
+        .or_else(|| known_checksum(&platform_revision));
+    match expected_checksum {
+        Some(expected_checksum) => {
+            if checksum != expected_checksum {
+                let _ = fs::remove_file(zip_path);
+                panic!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    url, expected_checksum, checksum
+                );
+            }
+        }
+        None if is_enabled("PUPPETEER_SKIP_CHROMIUM_CHECKSUM") => println!(
+            "cargo:warning=Not verifying {} (sha256 {}), PUPPETEER_SKIP_CHROMIUM_CHECKSUM is set",
+            url, checksum
+        ),
+        // TODO Fail for the default revision too once its checksums are in
+        // the manifest, rather than breaking every default build until then
+        None if is_default_revision => println!(
+            "cargo:warning=Not verifying {} (sha256 {}), no checksum is known for the default \
+             revision yet",
+            url, checksum
+        ),
+        None => {
+            let _ = fs::remove_file(zip_path);
+            panic!(
+                "No known checksum for chromium {} (downloaded from {}, sha256 {}). Add it to {} \
+                 or pass it in PUPPETEER_CHROMIUM_SHA256 once vetted, or set \
+                 PUPPETEER_SKIP_CHROMIUM_CHECKSUM=1 to extract it unverified",
+                platform_revision, url, checksum, CHECKSUMS
+            );
+        }
+    }
+
+    println!("Extracting chrome archive");
+    if extract_dir.exists() {
+        fs::remove_dir_all(extract_dir).expect("Failed to remove a previous extraction");
+    }
+    let mut file = File::open(zip_path).expect("Failed to open archive for reading");
+    extract_archive(&mut file, extract_dir);
+    fs::remove_file(zip_path).expect("Failed to clean up archive");
+
+    fs::rename(extract_dir.join(ARCHIVE_DIR), target_dir)
+        .expect("Failed to rename extracted chrome directory");
+    let _ = fs::remove_dir_all(extract_dir);
+
+    println!("Chrome downloaded to {}", target_dir.display());
+}
@@ -0,0 +1,640 @@
+//! Generates typed bindings for the DevTools protocol
+//!
+//! Reads the protocol description vendored in `protocol/` and writes one
+//! module per domain, with a struct per command, its return value and event,
+//! and a struct or enum per declared type.
+
+use serde_json::value::Value;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+const KEYWORDS: [&'static str; 41] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do",
+    "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe",
+];
+
+/// Identifiers that cannot be raw identifiers either
+const RESERVED: [&'static str; 4] = ["crate", "self", "Self", "super"];
+
+pub fn generate(protocol_files: &[&Path], out_file: &Path) {
+    let mut domains = Vec::new();
+    for protocol_file in protocol_files {
+        let json = fs::read_to_string(protocol_file).expect("Failed to read protocol file");
+        let mut protocol: Value = serde_json::from_str(&json).expect("Invalid protocol file");
+        if let Value::Array(found) = protocol["domains"].take() {
+            domains.extend(found);
+        }
+    }
+
+    let generator = Generator::new(&domains);
+    let mut out = String::new();
+    for domain in &domains {
+        generator.domain(domain, &mut out);
+    }
+
+    fs::write(out_file, out).expect("Failed to write protocol bindings");
+}
+
+struct Generator {
+    /// The types that are generated as structs, by qualified name
+    structs: HashSet<String>,
+    /// The struct fields that must be boxed to break a cycle, by qualified
+    /// name of the struct and name of the field
+    boxed: HashSet<(String, String)>,
+}
+
+impl Generator {
+    fn new(domains: &[Value]) -> Generator {
+        let mut structs = HashSet::new();
+        // Edges between structs that are not broken up by a `Vec`
+        let mut edges: HashMap<String, Vec<(String, String)>> = HashMap::new();
+
+        for domain in domains {
+            let domain_name = domain["domain"].as_str().unwrap();
+            for declared in iter(&domain["types"]) {
+                if declared["type"] == "object" && declared["properties"].is_array() {
+                    structs.insert(qualify(domain_name, declared["id"].as_str().unwrap()));
+                }
+            }
+        }
+
+        for domain in domains {
+            let domain_name = domain["domain"].as_str().unwrap();
+            for declared in iter(&domain["types"]) {
+                let name = qualify(domain_name, declared["id"].as_str().unwrap());
+                for property in iter(&declared["properties"]) {
+                    if let Some(target) = property["$ref"].as_str() {
+                        let target = qualify(domain_name, target);
+                        if structs.contains(&target) {
+                            edges.entry(name.clone()).or_default().push((
+                                property["name"].as_str().unwrap().to_string(),
+                                target,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut boxed = HashSet::new();
+        for (name, fields) in &edges {
+            for (field, target) in fields {
+                if reaches(&edges, target, name) {
+                    boxed.insert((name.clone(), field.clone()));
+                }
+            }
+        }
+
+        Generator {
+            structs: structs,
+            boxed: boxed,
+        }
+    }
+
+    fn domain(&self, domain: &Value, out: &mut String) {
+        let domain_name = domain["domain"].as_str().unwrap();
+        let mut names: HashSet<String> = iter(&domain["types"])
+            .map(|declared| declared["id"].as_str().unwrap().to_string())
+            .collect();
+
+        doc(&domain["description"], "", out);
+        out.push_str(&format!("pub mod {} {{\n", snake_case(domain_name)));
+        out.push_str("    #![allow(unused_imports)]\n");
+        out.push_str("    use super::*;\n\n");
+
+        for declared in iter(&domain["types"]) {
+            self.declared_type(domain_name, declared, &mut names, out);
+        }
+
+        for command in iter(&domain["commands"]) {
+            let command_name = command["name"].as_str().unwrap();
+            let mut struct_name = camel_case(command_name);
+            if names.contains(&struct_name) {
+                struct_name.push_str("Command");
+            }
+            names.insert(struct_name.clone());
+
+            let returns_name = format!("{}Returns", struct_name);
+            let returns_type = if iter(&command["returns"]).next().is_some() {
+                names.insert(returns_name.clone());
+                returns_name.clone()
+            } else {
+                "super::Empty".to_string()
+            };
+
+            let method = format!("{}.{}", domain_name, command_name);
+            self.object(
+                domain_name,
+                &struct_name,
+                &command["description"],
+                &command["parameters"],
+                true,
+                &mut names,
+                out,
+            );
+            out.push_str(&format!(
+                "    impl super::Command for {} {{\n        const NAME: &'static str = \"{}\";\n        type Returns = {};\n    }}\n\n",
+                struct_name, method, returns_type
+            ));
+
+            if returns_type == returns_name {
+                self.object(
+                    domain_name,
+                    &returns_name,
+                    &Value::Null,
+                    &command["returns"],
+                    false,
+                    &mut names,
+                    out,
+                );
+            }
+        }
+
+        for event in iter(&domain["events"]) {
+            let event_name = event["name"].as_str().unwrap();
+            let struct_name = format!("Event{}", camel_case(event_name));
+            names.insert(struct_name.clone());
+
+            self.object(
+                domain_name,
+                &struct_name,
+                &event["description"],
+                &event["parameters"],
+                false,
+                &mut names,
+                out,
+            );
+            out.push_str(&format!(
+                "    impl super::Event for {} {{\n        const NAME: &'static str = \"{}.{}\";\n    }}\n\n",
+                struct_name, domain_name, event_name
+            ));
+        }
+
+        out.push_str("}\n\n");
+    }
+
+    fn declared_type(
+        &self,
+        domain_name: &str,
+        declared: &Value,
+        names: &mut HashSet<String>,
+        out: &mut String,
+    ) {
+        let name = declared["id"].as_str().unwrap();
+        let description = &declared["description"];
+
+        if declared["enum"].is_array() {
+            doc(description, "    ", out);
+            enumeration(name, &declared["enum"], out);
+        } else if self.structs.contains(&qualify(domain_name, name)) {
+            let properties = &declared["properties"];
+            self.object(domain_name, name, description, properties, false, names, out);
+        } else {
+            let rust_type = self.rust_type(domain_name, name, "", declared, names, out);
+            doc(description, "    ", out);
+            out.push_str(&format!("    pub type {} = {};\n\n", name, rust_type));
+        }
+    }
+
+    /// Writes a struct with a field per property, and a constructor taking the
+    /// required properties if it is a command
+    ///
+    /// Enums for inline enumerations are written first, so that the
+    /// description ends up on the struct.
+    fn object(
+        &self,
+        domain_name: &str,
+        name: &str,
+        description: &Value,
+        properties: &Value,
+        is_command: bool,
+        names: &mut HashSet<String>,
+        out: &mut String,
+    ) {
+        let mut fields = String::new();
+        let mut required = Vec::new();
+        let mut optional = Vec::new();
+
+        for property in iter(properties) {
+            let property_name = property["name"].as_str().unwrap();
+            let field_name = field_name(property_name);
+            let mut rust_type =
+                self.rust_type(domain_name, name, property_name, property, names, out);
+            if self
+                .boxed
+                .contains(&(qualify(domain_name, name), property_name.to_string()))
+            {
+                rust_type = format!("Box<{}>", rust_type);
+            }
+
+            doc(&property["description"], "        ", &mut fields);
+            if field_name.trim_start_matches("r#") != property_name {
+                fields.push_str(&format!(
+                    "        #[serde(rename = \"{}\")]\n",
+                    property_name
+                ));
+            }
+            if property["optional"] == true {
+                fields.push_str(
+                    "        #[serde(default, skip_serializing_if = \"Option::is_none\")]\n",
+                );
+                fields.push_str(&format!("        pub {}: Option<{}>,\n", field_name, rust_type));
+                optional.push(field_name);
+            } else {
+                fields.push_str(&format!("        pub {}: {},\n", field_name, rust_type));
+                required.push((field_name, rust_type));
+            }
+        }
+
+        doc(description, "    ", out);
+        // Commands without required parameters can be built with `default()`
+        // as well as `new()`
+        let default = if is_command && required.is_empty() {
+            "Default, "
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "    #[derive(Debug, Clone, {}PartialEq, Serialize, Deserialize)]\n",
+            default
+        ));
+        out.push_str(&format!("    pub struct {} {{\n{}    }}\n\n", name, fields));
+
+        if is_command {
+            let arguments: Vec<String> = required
+                .iter()
+                .map(|(field_name, rust_type)| format!("{}: {}", field_name, rust_type))
+                .collect();
+            let mut initializers: Vec<String> = required
+                .iter()
+                .map(|(field_name, _)| format!("            {}: {},\n", field_name, field_name))
+                .collect();
+            initializers.extend(
+                optional
+                    .iter()
+                    .map(|field_name| format!("            {}: None,\n", field_name)),
+            );
+
+            out.push_str(&format!("    impl {} {{\n", name));
+            out.push_str(&format!(
+                "        pub fn new({}) -> {} {{\n            {} {{\n{}            }}\n        }}\n",
+                arguments.join(", "),
+                name,
+                name,
+                initializers
+                    .iter()
+                    .map(|initializer| format!("    {}", initializer))
+                    .collect::<String>()
+            ));
+            out.push_str("    }\n\n");
+        }
+    }
+
+    /// Maps a property or declared type to a Rust type, writing an enum for
+    /// inline enumerations
+    fn rust_type(
+        &self,
+        domain_name: &str,
+        owner: &str,
+        property_name: &str,
+        spec: &Value,
+        names: &mut HashSet<String>,
+        out: &mut String,
+    ) -> String {
+        if let Some(reference) = spec["$ref"].as_str() {
+            return match reference.find('.') {
+                Some(dot) => format!(
+                    "super::{}::{}",
+                    snake_case(&reference[..dot]),
+                    &reference[dot + 1..]
+                ),
+                None => reference.to_string(),
+            };
+        }
+
+        match spec["type"].as_str() {
+            Some("string") if spec["enum"].is_array() && !property_name.is_empty() => {
+                let mut enum_name = format!("{}{}", owner, camel_case(property_name));
+                while names.contains(&enum_name) {
+                    enum_name.push_str("Option");
+                }
+                names.insert(enum_name.clone());
+                enumeration(&enum_name, &spec["enum"], out);
+                enum_name
+            }
+            Some("string") | Some("binary") => "String".to_string(),
+            Some("integer") => "i64".to_string(),
+            Some("number") => "f64".to_string(),
+            Some("boolean") => "bool".to_string(),
+            Some("array") => {
+                let item = self.rust_type(domain_name, owner, "", &spec["items"], names, out);
+                format!("Vec<{}>", item)
+            }
+            _ => "serde_json::Value".to_string(),
+        }
+    }
+}
+
+/// Whether `to` can be reached from `from` through fields that are not boxed
+/// by a `Vec`
+fn reaches(edges: &HashMap<String, Vec<(String, String)>>, from: &str, to: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut pending = vec![from.to_string()];
+
+    while let Some(name) = pending.pop() {
+        if name == to {
+            return true;
+        }
+        if !visited.insert(name.clone()) {
+            continue;
+        }
+        if let Some(fields) = edges.get(&name) {
+            pending.extend(fields.iter().map(|(_, target)| target.clone()));
+        }
+    }
+
+    false
+}
+
+/// Writes an enum with a variant per value
+///
+/// Values added by newer versions of Chrome are deserialized as `Unknown`,
+/// rather than failing the whole message. If `unknown` is one of the values,
+/// its variant doubles as the catch-all.
+fn enumeration(name: &str, values: &Value, out: &mut String) {
+    out.push_str("    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]\n");
+    out.push_str(&format!("    pub enum {} {{\n", name));
+
+    let mut variants = HashSet::new();
+    let mut catch_all = None;
+    for value in iter(values) {
+        let value = value.as_str().unwrap();
+        let mut variant = camel_case(value);
+        if variant.is_empty() || variant.starts_with(|c: char| c.is_ascii_digit()) {
+            variant = format!("V{}", variant);
+        }
+        if RESERVED.contains(&variant.as_str()) {
+            variant.push('_');
+        }
+        while !variants.insert(variant.clone()) {
+            variant.push('_');
+        }
+        if variant == "Unknown" {
+            catch_all = Some(value);
+        } else {
+            out.push_str(&format!(
+                "        #[serde(rename = \"{}\")]\n        {},\n",
+                value, variant
+            ));
+        }
+    }
+
+    // The catch-all must be the last variant
+    match catch_all {
+        Some(value) => out.push_str(&format!("        #[serde(rename = \"{}\")]\n", value)),
+        None => out.push_str("        /// A value this version of the bindings does not know of\n"),
+    }
+    out.push_str("        #[serde(other)]\n        Unknown,\n");
+
+    out.push_str("    }\n\n");
+}
+
+fn doc(description: &Value, indent: &str, out: &mut String) {
+    if let Some(description) = description.as_str() {
+        for line in description.lines() {
+            // Indented lines and fences would be compiled as doctests
+            let line = line.trim().replace("```", "");
+            out.push_str(&format!("{}/// {}\n", indent, line).replace("/// \n", "///\n"));
+        }
+    }
+}
+
+fn iter<'a>(value: &'a Value) -> impl Iterator<Item = &'a Value> {
+    value.as_array().into_iter().flat_map(|values| values.iter())
+}
+
+fn qualify(domain_name: &str, name: &str) -> String {
+    if name.contains('.') {
+        name.to_string()
+    } else {
+        format!("{}.{}", domain_name, name)
+    }
+}
+
+fn field_name(name: &str) -> String {
+    let name = snake_case(name);
+    if RESERVED.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else if KEYWORDS.contains(&name.as_str()) {
+        format!("r#{}", name)
+    } else {
+        name
+    }
+}
+
+/// Converts `DOMDebugger` to `dom_debugger` and `backendNodeId` to
+/// `backend_node_id`
+fn snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::new();
+
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '-' || c == '_' || c == ' ' || c == '.' {
+            if !snake.is_empty() && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            continue;
+        }
+        if c.is_ascii_uppercase() && i > 0 {
+            let previous = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).map_or(false, |n| n.is_ascii_lowercase());
+            if (previous.is_ascii_lowercase() || previous.is_ascii_digit())
+                || (previous.is_ascii_uppercase() && next_is_lower)
+            {
+                if !snake.ends_with('_') {
+                    snake.push('_');
+                }
+            }
+        }
+        snake.push(c.to_ascii_lowercase());
+    }
+
+    snake
+}
+
+/// Converts `navigate` to `Navigate` and `script-blocked` to `ScriptBlocked`
+fn camel_case(name: &str) -> String {
+    let mut camel = String::new();
+    let mut capitalize = true;
+
+    for c in name.chars() {
+        if !c.is_ascii_alphanumeric() {
+            capitalize = true;
+            continue;
+        }
+        if capitalize {
+            camel.push(c.to_ascii_uppercase());
+            capitalize = false;
+        } else {
+            camel.push(c);
+        }
+    }
+
+    camel
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use super::{camel_case, field_name, snake_case, Generator};
+    use serde_json::value::Value;
+
+    /// Generates the bindings of the domains in `protocol`
+    fn generate(protocol: Value) -> String {
+        let domains = protocol["domains"].as_array().unwrap();
+        let generator = Generator::new(domains);
+        let mut out = String::new();
+        for domain in domains {
+            generator.domain(domain, &mut out);
+        }
+        out
+    }
+
+    #[test]
+    fn test_naming() {
+        let _ = env_logger::try_init();
+
+        assert_eq!(snake_case("DOMDebugger"), "dom_debugger");
+        assert_eq!(snake_case("backendNodeId"), "backend_node_id");
+        assert_eq!(snake_case("IndexedDB"), "indexed_db");
+        assert_eq!(camel_case("navigate"), "Navigate");
+        assert_eq!(camel_case("script-blocked"), "ScriptBlocked");
+        assert_eq!(field_name("type"), "r#type");
+        assert_eq!(field_name("self"), "self_");
+        assert_eq!(field_name("frameId"), "frame_id");
+    }
+
+    #[test]
+    fn test_command_params() {
+        let _ = env_logger::try_init();
+
+        let out = generate(json!({
+            "domains": [{
+                "domain": "Page",
+                "commands": [
+                    {
+                        "name": "navigate",
+                        "parameters": [
+                            { "name": "url", "type": "string" },
+                            { "name": "referrer", "type": "string", "optional": true },
+                        ],
+                        "returns": [{ "name": "frameId", "type": "string" }],
+                    },
+                    { "name": "enable" },
+                ],
+            }]
+        }));
+
+        // Required parameters are arguments of `new`, optional ones `None`
+        assert!(out.contains("pub fn new(url: String) -> Navigate {"));
+        assert!(out.contains("                url: url,\n                referrer: None,\n"));
+        assert!(out.contains(
+            "        #[serde(default, skip_serializing_if = \"Option::is_none\")]\n        \
+             pub referrer: Option<String>,\n"
+        ));
+        assert!(out.contains(
+            "        #[serde(rename = \"frameId\")]\n        pub frame_id: String,\n"
+        ));
+        assert!(out.contains("const NAME: &'static str = \"Page.navigate\";"));
+        assert!(out.contains("type Returns = NavigateReturns;"));
+        // Only commands without required parameters derive `Default`
+        assert!(out.contains(
+            "    #[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]\n    \
+             pub struct Enable {"
+        ));
+        assert!(out.contains("type Returns = super::Empty;"));
+        let navigate = "Default, PartialEq, Serialize, Deserialize)]\n    pub struct Navigate {";
+        assert!(!out.contains(navigate));
+    }
+
+    #[test]
+    fn test_refs() {
+        let _ = env_logger::try_init();
+
+        let out = generate(json!({
+            "domains": [
+                {
+                    "domain": "Network",
+                    "types": [{ "id": "LoaderId", "type": "string" }],
+                },
+                {
+                    "domain": "Page",
+                    "types": [
+                        { "id": "FrameId", "type": "string" },
+                        {
+                            "id": "Frame",
+                            "type": "object",
+                            "properties": [
+                                { "name": "id", "$ref": "FrameId" },
+                                { "name": "loaderId", "$ref": "Network.LoaderId" },
+                                { "name": "parent", "$ref": "Frame", "optional": true },
+                                {
+                                    "name": "children",
+                                    "type": "array",
+                                    "items": { "$ref": "Frame" },
+                                },
+                            ],
+                        },
+                    ],
+                },
+            ]
+        }));
+
+        assert!(out.contains("pub type LoaderId = String;"));
+        assert!(out.contains("pub id: FrameId,"));
+        // References to other domains go through their module
+        assert!(out.contains("pub loader_id: super::network::LoaderId,"));
+        // A struct containing itself is boxed, unless a `Vec` breaks the cycle
+        assert!(out.contains("pub parent: Option<Box<Frame>>,"));
+        assert!(out.contains("pub children: Vec<Frame>,"));
+    }
+
+    #[test]
+    fn test_enums() {
+        let _ = env_logger::try_init();
+
+        let out = generate(json!({
+            "domains": [{
+                "domain": "Runtime",
+                "types": [
+                    { "id": "Kind", "type": "string", "enum": ["array", "null", "unknown"] },
+                    {
+                        "id": "RemoteObject",
+                        "type": "object",
+                        "properties": [
+                            { "name": "type", "type": "string", "enum": ["object", "self", "3d"] },
+                        ],
+                    },
+                ],
+            }]
+        }));
+
+        assert!(out.contains("pub enum Kind {"));
+        assert!(out.contains("        #[serde(rename = \"array\")]\n        Array,\n"));
+        // An `unknown` value doubles as the catch-all, which must come last
+        assert!(out.contains(
+            "        #[serde(rename = \"null\")]\n        Null,\n        \
+             #[serde(rename = \"unknown\")]\n        #[serde(other)]\n        Unknown,\n    }"
+        ));
+        // Inline enums are named after the struct and property
+        assert!(out.contains("pub r#type: RemoteObjectType,"));
+        assert!(out.contains("pub enum RemoteObjectType {"));
+        assert!(out.contains("        Self_,\n"));
+        assert!(out.contains("        #[serde(rename = \"3d\")]\n        V3d,\n"));
+        assert!(out.contains("        #[serde(other)]\n        Unknown,\n    }"));
+    }
+}
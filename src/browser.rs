@@ -1,25 +1,488 @@
+use crate::browser_context::BrowserContext;
 use crate::connection::Connection;
+use crate::error::Error;
+use crate::helper;
+use crate::launcher::Viewport;
 use crate::page::Page;
+use crate::protocol::{browser, target};
+use crate::target::{Target, TargetEvent, TargetType};
+#[cfg(unix)]
+use crate::signals;
+use crate::transport::EventStream;
+use futures::future;
+use futures::{Future, FutureExt, Stream, StreamExt};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
 use std::process::Child;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
+/// How long `close` waits for the browser to exit before killing it
+const CLOSE_GRACE_PERIOD_MS: u32 = 5000;
+
+/// How long `new_page` waits for the browser to report the new tab
+const NEW_PAGE_TIMEOUT_MS: u32 = 30000;
+
+/// A browser, either launched by the `Launcher` or connected to
+///
+/// Only launched browsers own their process. `close` ends the browser either
+/// way, while `disconnect` leaves it running for others to connect to.
 pub struct Browser {
     pub connection: Connection,
-    pub child_process: Child,
+    pub child_process: Option<Child>,
+    temporary_user_data_dir: Option<PathBuf>,
+    ignore_https_errors: bool,
+    default_viewport: Option<Viewport>,
+    targets: Mutex<TargetRegistry>,
+    /// Whether `disconnect` was called since connecting, in which case the
+    /// browser is left running when this is dropped
+    disconnected: bool,
+    /// The ids of the incognito browser contexts that have not been closed, as
+    /// of connecting and the changes made through this browser since
+    browser_context_ids: Mutex<Vec<String>>,
+}
+
+/// The targets of the browser, kept up to date from the target events that
+/// have been received so far
+struct TargetRegistry {
+    events: EventStream,
+    targets: HashMap<String, Target>,
+    /// The pages attached to so far, so that each tab gets a single session
+    pages: HashMap<String, Page>,
+}
+
+impl TargetRegistry {
+    fn new(connection: &Connection) -> TargetRegistry {
+        TargetRegistry {
+            events: connection.subscribe(&TargetEvent::METHODS),
+            targets: HashMap::new(),
+            pages: HashMap::new(),
+        }
+    }
+
+    /// Applies the events received since the last update
+    fn update(&mut self, connection: &Connection) {
+        while let Ok(Some(event)) = self.events.try_next() {
+            match TargetEvent::from_event(connection, event) {
+                Some(TargetEvent::Created(target)) | Some(TargetEvent::Changed(target)) => {
+                    self.targets.insert(target.target_id().to_string(), target);
+                }
+                Some(TargetEvent::Destroyed(target_id)) => {
+                    self.targets.remove(&target_id);
+                    self.pages.remove(&target_id);
+                }
+                None => (),
+            }
+        }
+    }
 }
 
 impl Browser {
-    pub fn new(connection: Connection, child_process: Child) -> Browser {
-        Browser {
+    /// Wraps a connection to a browser and starts tracking its targets
+    ///
+    /// If the browser cannot be asked for its targets, it is dropped, which
+    /// kills `child_process`.
+    pub async fn new(
+        connection: Connection,
+        child_process: Option<Child>,
+        temporary_user_data_dir: Option<PathBuf>,
+        ignore_https_errors: bool,
+        default_viewport: Option<Viewport>,
+    ) -> Result<Browser, Error> {
+        let targets = TargetRegistry::new(&connection);
+
+        let browser = Browser {
             connection: connection,
             child_process: child_process,
+            temporary_user_data_dir: temporary_user_data_dir,
+            ignore_https_errors: ignore_https_errors,
+            default_viewport: default_viewport,
+            targets: Mutex::new(targets),
+            disconnected: false,
+            browser_context_ids: Mutex::new(Vec::new()),
+        };
+        await!(browser.load_browser_contexts())?;
+        await!(browser.discover_targets())?;
+
+        Ok(browser)
+    }
+
+    /// Looks up the incognito browser contexts, including those created by
+    /// other clients of the browser
+    async fn load_browser_contexts(&self) -> Result<(), Error> {
+        let get_browser_contexts = target::GetBrowserContexts::new();
+        let browser_context_ids = await!(self.connection.send(get_browser_contexts))?
+            .browser_context_ids;
+        *self.browser_context_ids.lock().unwrap() = browser_context_ids;
+        Ok(())
+    }
+
+    /// Asks the browser to report its targets, which it does for the existing
+    /// ones before replying
+    async fn discover_targets(&self) -> Result<(), Error> {
+        await!(self.connection.send(target::SetDiscoverTargets::new(true)))?;
+        Ok(())
+    }
+
+    /// Returns the product name and version of the browser, such as
+    /// `HeadlessChrome/73.0.3679.0`
+    pub async fn version(&self) -> Result<String, Error> {
+        Ok(await!(self.connection.send(browser::GetVersion::new()))?.product)
+    }
+
+    /// Returns the default user agent of the browser's pages
+    pub async fn user_agent(&self) -> Result<String, Error> {
+        Ok(await!(self.connection.send(browser::GetVersion::new()))?.user_agent)
+    }
+
+    /// Returns the websocket endpoint other clients can connect to with
+    /// `Handle::connect`, or `None` if the browser was launched with `pipe`
+    pub fn ws_endpoint(&self) -> Option<&str> {
+        self.connection.ws_endpoint()
+    }
+
+    /// Returns the pid of the browser process, or `None` if we did not launch
+    /// it
+    pub fn process(&self) -> Option<u32> {
+        self.child_process.as_ref().map(|child_process| child_process.id())
+    }
+
+    /// Opens a new tab in the default browser context and attaches a page
+    /// session to it
+    pub async fn new_page(&self) -> Result<Page, Error> {
+        await!(self.create_page(None))
+    }
+
+    /// Creates a browser context that shares no cookies or storage with the
+    /// others, and whose data is deleted when it is closed
+    pub async fn create_incognito_browser_context<'a>(
+        &'a self,
+    ) -> Result<BrowserContext<'a>, Error> {
+        let create_browser_context = target::CreateBrowserContext::new();
+        let browser_context_id = await!(self.connection.send(create_browser_context))?
+            .browser_context_id;
+        self.browser_context_ids
+            .lock()
+            .unwrap()
+            .push(browser_context_id.clone());
+
+        Ok(BrowserContext::new(self, Some(browser_context_id)))
+    }
+
+    /// Returns the default browser context followed by the incognito ones
+    pub fn browser_contexts(&self) -> Vec<BrowserContext<'_>> {
+        let mut browser_contexts = vec![self.default_browser_context()];
+        for browser_context_id in self.browser_context_ids.lock().unwrap().iter() {
+            browser_contexts.push(BrowserContext::new(self, Some(browser_context_id.clone())));
+        }
+        browser_contexts
+    }
+
+    /// Returns the browser context the browser started with
+    pub fn default_browser_context(&self) -> BrowserContext<'_> {
+        BrowserContext::new(self, None)
+    }
+
+    /// Opens a new tab in a browser context, the default one if `None`
+    pub(crate) async fn create_page(
+        &self,
+        browser_context_id: Option<String>,
+    ) -> Result<Page, Error> {
+        let mut create_target = target::CreateTarget::new("about:blank".to_string());
+        create_target.browser_context_id = browser_context_id;
+        let target_id = await!(self.connection.send(create_target))?.target_id;
+
+        let is_created = move |target: &Target| target.target_id() == target_id;
+        let target = await!(self.wait_for_target(is_created, NEW_PAGE_TIMEOUT_MS))?;
+        let page = await!(self.attach_page(target, true))?;
+        Ok(self.cache_page(page))
+    }
+
+    /// Forgets an incognito browser context after it has been disposed of
+    pub(crate) fn remove_browser_context(&self, browser_context_id: &str) {
+        self.browser_context_ids
+            .lock()
+            .unwrap()
+            .retain(|id| id != browser_context_id);
+    }
+
+    /// Whether a target belongs to the given browser context, or to the
+    /// default one if `browser_context_id` is `None`
+    pub(crate) fn is_in_browser_context(
+        &self,
+        target: &Target,
+        browser_context_id: Option<&str>,
+    ) -> bool {
+        match browser_context_id {
+            Some(browser_context_id) => target.browser_context_id() == Some(browser_context_id),
+            // The targets of the default context have an id too, just not one
+            // of ours
+            None => match target.browser_context_id() {
+                Some(id) => !self
+                    .browser_context_ids
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|browser_context_id| browser_context_id == id),
+                None => true,
+            },
+        }
+    }
+
+    /// Returns the targets of the browser, such as tabs and service workers
+    pub fn targets(&self) -> Vec<Target> {
+        let mut targets = self.targets.lock().unwrap();
+        targets.update(&self.connection);
+        targets.targets.values().cloned().collect()
+    }
+
+    /// Returns the open tabs
+    ///
+    /// A session is attached to each tab the first time it is returned, and
+    /// reused by later calls and by the `Page` returned from `new_page`.
+    pub async fn pages(&self) -> Result<Vec<Page>, Error> {
+        let mut pages = Vec::new();
+        for target in self.targets() {
+            if target.target_type() == &TargetType::Page {
+                pages.push(await!(self.page(target))?);
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Returns the page of a target of type page, attaching to it unless it
+    /// has been already
+    pub(crate) async fn page(&self, target: Target) -> Result<Page, Error> {
+        if let Some(page) = self.targets.lock().unwrap().pages.get(target.target_id()) {
+            return Ok(page.clone());
+        }
+
+        let page = await!(self.attach_page(target, false))?;
+        let cached = self.cache_page(page.clone());
+        if cached.session_id() != page.session_id() {
+            // Another call attached to the target in the meantime
+            let _ = await!(page.session().detach());
         }
+        Ok(cached)
+    }
+
+    /// Keeps a page for later calls, returning the page already kept for its
+    /// target if there is one
+    fn cache_page(&self, page: Page) -> Page {
+        let mut targets = self.targets.lock().unwrap();
+        targets
+            .pages
+            .entry(page.target_id().to_string())
+            .or_insert(page)
+            .clone()
     }
 
-    pub fn new_page(&self) -> Page {
-        unimplemented!();
+    /// Returns a stream of the targets as they are created, changed and
+    /// destroyed
+    pub fn target_events(&self) -> impl Stream<Item = TargetEvent> {
+        let connection = self.connection.clone();
+        self.connection
+            .subscribe(&TargetEvent::METHODS)
+            .filter_map(move |event| future::ready(TargetEvent::from_event(&connection, event)))
     }
 
-    pub async fn close(&mut self) {
-        self.child_process.kill();
+    /// Resolves to the first target for which `predicate` returns true, be it
+    /// an existing one or one created or changed within `timeout` milliseconds
+    pub async fn wait_for_target<'a, P>(
+        &'a self,
+        predicate: P,
+        timeout: u32,
+    ) -> Result<Target, Error>
+    where
+        P: Fn(&Target) -> bool + 'a,
+    {
+        // Subscribe before looking at the existing targets so that none is missed
+        let mut events = self.target_events();
+        if let Some(target) = self.targets().into_iter().find(|target| predicate(target)) {
+            return Ok(target);
+        }
+
+        let predicate = &predicate;
+        let wait = async move {
+            while let Some(event) = await!(events.next()) {
+                match event {
+                    TargetEvent::Created(target) | TargetEvent::Changed(target) => {
+                        if predicate(&target) {
+                            return Some(target);
+                        }
+                    }
+                    TargetEvent::Destroyed(_) => (),
+                }
+            }
+            None
+        };
+
+        match await!(helper::timeout(wait, timeout))? {
+            Some(Some(target)) => Ok(target),
+            Some(None) => Err(Error::Connection(
+                "The connection closed while waiting for a target".to_string(),
+            )),
+            None => Err(Error::Timeout(format!(
+                "Waiting for a target exceeded {} ms",
+                timeout
+            ))),
+        }
+    }
+
+    /// Attaches a page session to a target of type page, applying the
+    /// default viewport if the page is new
+    pub(crate) async fn attach_page(&self, target: Target, new: bool) -> Result<Page, Error> {
+        let session = await!(target.create_cdp_session())?;
+        let page = Page::new(target, session);
+        await!(page.initialize(self.ignore_https_errors))?;
+        if new {
+            if let Some(viewport) = &self.default_viewport {
+                await!(page.set_viewport(viewport))?;
+            }
+        }
+        Ok(page)
+    }
+
+    /// Closes the browser
+    ///
+    /// Asks the browser to close with `Browser.close`, connecting to it again
+    /// first after `disconnect`. If we launched it, it is killed if it has not
+    /// exited within a grace period, or right away if it could not be asked,
+    /// and the temporary profile created for it is deleted.
+    pub async fn close(&mut self) -> Result<(), Error> {
+        // `disconnect` closed the transport, which `Browser.close` needs
+        if self.disconnected {
+            match self.connection.reconnect() {
+                Ok(connection) => {
+                    self.connection = connection;
+                    self.disconnected = false;
+                }
+                Err(e) => warn!("Failed to reconnect to close the browser: {}", e),
+            }
+        }
+
+        // The grace period covers both the reply and the exit
+        let mut deadline =
+            Instant::now() + Duration::from_millis(u64::from(CLOSE_GRACE_PERIOD_MS));
+        if self.disconnected {
+            // Nothing asked the browser to exit, so there is no point waiting
+            deadline = Instant::now();
+        } else {
+            // Chrome may drop the connection before it gets to reply, or not
+            // reply at all if it hangs
+            let _ = await!(helper::timeout(
+                self.connection.send(browser::Close::new()),
+                CLOSE_GRACE_PERIOD_MS,
+            ));
+        }
+        self.connection.close();
+
+        if let Some(child_process) = &mut self.child_process {
+            #[cfg(unix)]
+            signals::unregister(child_process.id());
+            await!(Browser::wait_or_kill(child_process, deadline))?;
+        }
+
+        self.remove_temporary_user_data_dir()
+    }
+
+    /// Closes the connection to the browser, but leaves it running
+    ///
+    /// Use `reconnect` or `Handle::connect` with the `ws_endpoint` to connect
+    /// to it again. Note that a browser launched with `pipe` exits once its
+    /// pipe closes.
+    ///
+    /// If we launched the browser, we still own its process: `close` ends it
+    /// and deletes its temporary profile, with or without `reconnect` first.
+    /// Dropping a disconnected browser instead leaves it running, so its
+    /// process is never reaped by us and its temporary profile is left in
+    /// place.
+    pub fn disconnect(&mut self) {
+        self.disconnected = true;
+        self.connection.close();
+    }
+
+    /// Connects to the browser again through its websocket endpoint, after
+    /// `disconnect` or after the connection dropped
+    pub async fn reconnect(&mut self) -> Result<(), Error> {
+        self.connection.close();
+        self.connection = self.connection.reconnect()?;
+        self.disconnected = false;
+        *self.targets.lock().unwrap() = TargetRegistry::new(&self.connection);
+        await!(self.load_browser_contexts())?;
+        await!(self.discover_targets())
+    }
+
+    /// Resolves once the connection to the browser closes, be it through
+    /// `disconnect` or `close`, or because the browser or the socket went away
+    pub fn disconnected(&self) -> impl Future<Output = ()> {
+        self.connection.subscribe(&[]).into_future().map(|_| ())
+    }
+
+    /// Waits for the process to exit until `deadline`, and kills it if it has
+    /// not
+    async fn wait_or_kill(child_process: &mut Child, deadline: Instant) -> Result<(), Error> {
+        while Instant::now() < deadline {
+            if child_process.try_wait()?.is_some() {
+                return Ok(());
+            }
+            if let Err(e) = await!(helper::sleep(50)) {
+                // Rather than leave the browser running
+                warn!("Cannot wait for the browser to exit: {}", e);
+                break;
+            }
+        }
+
+        warn!("Browser did not exit within {} ms, killing it", CLOSE_GRACE_PERIOD_MS);
+        // Fails if the process exited in the meantime, which is what we want
+        let _ = child_process.kill();
+        child_process.wait()?;
+        Ok(())
+    }
+
+    fn remove_temporary_user_data_dir(&mut self) -> Result<(), Error> {
+        if let Some(user_data_dir) = self.temporary_user_data_dir.take() {
+            match fs::remove_dir_all(&user_data_dir) {
+                Err(ref e) if e.kind() != io::ErrorKind::NotFound => {
+                    return Err(Error::Io(io::Error::new(
+                        e.kind(),
+                        format!("Failed to remove {}: {}", user_data_dir.display(), e),
+                    )));
+                }
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Browser {
+    /// Kills the browser if we launched it and it is still running, e.g.
+    /// because a test panicked before calling `close`, unless it has been
+    /// disconnected from
+    fn drop(&mut self) {
+        if self.disconnected {
+            if let Some(child_process) = &self.child_process {
+                #[cfg(unix)]
+                signals::unregister(child_process.id());
+                info!("Leaving the disconnected browser {} running", child_process.id());
+            }
+            return;
+        }
+
+        if let Some(child_process) = &mut self.child_process {
+            #[cfg(unix)]
+            signals::unregister(child_process.id());
+            if let Ok(None) = child_process.try_wait() {
+                let _ = child_process.kill();
+                let _ = child_process.wait();
+            }
+        }
+
+        if let Err(e) = self.remove_temporary_user_data_dir() {
+            warn!("{}", e);
+        }
     }
 }
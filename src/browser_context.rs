@@ -0,0 +1,107 @@
+use crate::browser::Browser;
+use crate::error::Error;
+use crate::page::Page;
+use crate::protocol::browser::{GrantPermissions, PermissionType, ResetPermissions};
+use crate::protocol::target::DisposeBrowserContext;
+use crate::target::{Target, TargetType};
+
+/// A set of tabs sharing cookies, storage and permissions
+///
+/// The default browser context is the one the browser starts with. Incognito
+/// contexts share nothing with it or with each other, so independent jobs can
+/// run side by side in one browser.
+pub struct BrowserContext<'a> {
+    browser: &'a Browser,
+    /// `None` for the default browser context
+    id: Option<String>,
+}
+
+impl<'a> BrowserContext<'a> {
+    pub fn new(browser: &'a Browser, id: Option<String>) -> BrowserContext<'a> {
+        BrowserContext {
+            browser: browser,
+            id: id,
+        }
+    }
+
+    /// The id of an incognito browser context, `None` for the default one
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_ref().map(String::as_str)
+    }
+
+    pub fn is_incognito(&self) -> bool {
+        self.id.is_some()
+    }
+
+    pub fn browser(&self) -> &'a Browser {
+        self.browser
+    }
+
+    /// Opens a new tab in the browser context
+    pub async fn new_page(&self) -> Result<Page, Error> {
+        await!(self.browser.create_page(self.id.clone()))
+    }
+
+    /// Returns the targets of the browser context
+    pub fn targets(&self) -> Vec<Target> {
+        self.browser
+            .targets()
+            .into_iter()
+            .filter(|target| self.browser.is_in_browser_context(target, self.id()))
+            .collect()
+    }
+
+    /// Returns the open tabs of the browser context
+    ///
+    /// Like `Browser::pages`, each tab keeps the session attached to it first.
+    pub async fn pages(&self) -> Result<Vec<Page>, Error> {
+        let mut pages = Vec::new();
+        for target in self.targets() {
+            if target.target_type() == &TargetType::Page {
+                pages.push(await!(self.browser.page(target))?);
+            }
+        }
+        Ok(pages)
+    }
+
+    /// Grants `permissions` to `origin`, such as `https://example.com`, and
+    /// denies it all others
+    pub async fn override_permissions<'b>(
+        &'b self,
+        origin: &'b str,
+        permissions: Vec<PermissionType>,
+    ) -> Result<(), Error> {
+        let mut grant_permissions = GrantPermissions::new(permissions);
+        grant_permissions.origin = Some(origin.to_string());
+        grant_permissions.browser_context_id = self.id.clone();
+        await!(self.browser.connection.send(grant_permissions))?;
+        Ok(())
+    }
+
+    /// Resets the permissions of every origin to their defaults
+    pub async fn clear_permission_overrides(&self) -> Result<(), Error> {
+        let mut reset_permissions = ResetPermissions::new();
+        reset_permissions.browser_context_id = self.id.clone();
+        await!(self.browser.connection.send(reset_permissions))?;
+        Ok(())
+    }
+
+    /// Closes the tabs of an incognito browser context and deletes its data
+    ///
+    /// The default browser context cannot be closed, which fails with
+    /// `Error::Unsupported`.
+    pub async fn close(self) -> Result<(), Error> {
+        let id = match self.id {
+            Some(id) => id,
+            None => {
+                return Err(Error::Unsupported(
+                    "The default browser context cannot be closed".to_string(),
+                ));
+            }
+        };
+
+        await!(self.browser.connection.send(DisposeBrowserContext::new(id.clone())))?;
+        self.browser.remove_browser_context(&id);
+        Ok(())
+    }
+}
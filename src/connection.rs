@@ -1,27 +1,188 @@
+use crate::error::{Error, ProtocolError};
+use crate::helper;
 use crate::message::Message;
-use crate::ws::{Response, WebSocketTransport};
+use crate::pipe::PipeTransport;
+use crate::protocol::Command;
+use crate::transport::{self, EventStream, Registration, Response, Transport};
+use crate::ws::WebSocketTransport;
 use serde_json::value::Value;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
+/// A connection to the browser endpoint
+///
+/// Connections are cheap to clone, so that pages and other targets can share
+/// the transport of the browser they were created from.
+#[derive(Clone)]
 pub struct Connection {
-    browser_WS_endpoint: String,
-    transport: WebSocketTransport,
+    browser_WS_endpoint: Option<String>,
+    transport: Arc<dyn Transport>,
+    registration: Registration,
+    counter: Arc<AtomicUsize>,
     slow_mo: u32,
 }
 
 impl Connection {
     pub fn new(
-        browser_WS_endpoint: String,
-        transport: WebSocketTransport,
+        browser_WS_endpoint: Option<String>,
+        transport: Arc<dyn Transport>,
+        registration: Registration,
         slow_mo: u32,
     ) -> Connection {
         Connection {
             browser_WS_endpoint: browser_WS_endpoint,
             transport: transport,
+            registration: registration,
+            counter: Arc::new(AtomicUsize::new(0)),
             slow_mo: slow_mo,
         }
     }
 
-    pub async fn send(&mut self, msg: Message) -> Response {
-        await!(self.transport.send(msg))
+    /// Connects to the browser over websocket
+    pub fn connect(browser_WS_endpoint: String, slow_mo: u32) -> Result<Connection, Error> {
+        let (registration, dispatcher) = transport::dispatcher();
+        let transport = WebSocketTransport::new(browser_WS_endpoint.clone(), dispatcher)?;
+
+        Ok(Connection::new(
+            Some(browser_WS_endpoint),
+            Arc::new(transport),
+            registration,
+            slow_mo,
+        ))
+    }
+
+    /// Opens a new connection to the websocket endpoint of this one, e.g.
+    /// after it closed
+    pub fn reconnect(&self) -> Result<Connection, Error> {
+        match &self.browser_WS_endpoint {
+            Some(browser_WS_endpoint) => {
+                Connection::connect(browser_WS_endpoint.clone(), self.slow_mo)
+            }
+            None => Err(Error::Connection(
+                "Connections over a pipe cannot be reopened".to_string(),
+            )),
+        }
+    }
+
+    /// Connects to a browser launched with `--remote-debugging-pipe`
+    pub fn from_pipe<W, R>(writer: W, reader: R, slow_mo: u32) -> Result<Connection, Error>
+    where
+        W: Write + Send + 'static,
+        R: Read + Send + 'static,
+    {
+        let (registration, dispatcher) = transport::dispatcher();
+        let transport = PipeTransport::new(writer, reader, dispatcher)?;
+
+        Ok(Connection::new(None, Arc::new(transport), registration, slow_mo))
+    }
+
+    /// Sends a command and resolves to Chrome's reply
+    pub async fn send<C: Command>(&self, command: C) -> Result<C::Returns, Error> {
+        let result = await!(self.send_raw(Message::from_command(&command)))?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Sends a message and resolves to the untyped `result` of Chrome's reply
+    ///
+    /// Use this with `Message::raw` for methods without a typed command. Error
+    /// replies are returned as `Error::Protocol`, tagged with the method of the
+    /// message.
+    ///
+    /// With `slow_mo` set, the message is held back for that many milliseconds
+    /// first. Only this call waits, other sends go ahead in the meantime.
+    pub async fn send_raw(&self, msg: Message) -> Result<Value, Error> {
+        if self.slow_mo > 0 {
+            await!(helper::sleep(self.slow_mo))?;
+        }
+
+        let method = msg.method().to_string();
+        let id = self.generate_id();
+        let message = msg.serialize(id);
+        info!("Sending message {}", message);
+
+        let session_id = msg.session_id().map(String::from);
+        let reply = self.registration.register_callback(id, session_id);
+        self.transport.send(message)?;
+        let reply = await!(reply).map_err(|_| {
+            Error::Connection(format!("The connection closed before Chrome replied to {}", method))
+        })?;
+
+        match reply {
+            Response::Frame(mut data) => {
+                if data["error"].is_object() {
+                    Err(ProtocolError::from_value(method, &data["error"]).into())
+                } else {
+                    Ok(data["result"].take())
+                }
+            }
+            Response::Meta(event) => Err(Error::Connection(format!(
+                "Expected a reply to {}, got {:?}",
+                method, event
+            ))),
+            Response::TargetClosed => Err(Error::TargetClosed),
+        }
+    }
+
+    /// Returns a stream of the events with any of the given method names,
+    /// regardless of the session that emitted them
+    pub fn subscribe(&self, methods: &[&str]) -> EventStream {
+        self.registration.subscribe(methods, None)
+    }
+
+    /// Returns a stream of the events with any of the given method names that
+    /// were emitted by the target attached with `session_id`
+    pub fn subscribe_session(&self, session_id: &str, methods: &[&str]) -> EventStream {
+        self.registration.subscribe(methods, Some(session_id.to_string()))
+    }
+
+    /// The websocket endpoint connected to, `None` when connected over a pipe
+    pub fn ws_endpoint(&self) -> Option<&str> {
+        self.browser_WS_endpoint.as_ref().map(String::as_str)
+    }
+
+    /// Closes the transport shared by every clone of this connection
+    pub fn close(&self) {
+        self.transport.close();
+    }
+
+    fn generate_id(&self) -> u64 {
+        (self.counter.fetch_add(1, Ordering::SeqCst) + 1) as u64
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    extern crate env_logger;
+
+    use crate::connection::Connection;
+    use crate::message::Message;
+    use futures::executor::block_on;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_slow_mo() {
+        let _ = env_logger::try_init();
+
+        let (writer, chrome_reader) = UnixStream::pair().unwrap();
+        let (reader, mut chrome_writer) = UnixStream::pair().unwrap();
+        let connection = Connection::from_pipe(writer, reader, 200).unwrap();
+
+        let start = Instant::now();
+        let chrome = thread::spawn(move || {
+            let mut frame = Vec::new();
+            BufReader::new(chrome_reader)
+                .read_until(b'\0', &mut frame)
+                .unwrap();
+            let received = start.elapsed();
+            chrome_writer.write_all(b"{\"id\":1,\"result\":{}}\0").unwrap();
+            received
+        });
+
+        block_on(connection.send_raw(Message::raw("Browser.getVersion", json!({})))).unwrap();
+        assert!(chrome.join().unwrap() >= Duration::from_millis(200));
     }
 }
@@ -0,0 +1,275 @@
+//! Errors surfaced by the crate
+
+use crate::protocol::runtime::{CallFrame, ExceptionDetails};
+use serde_json::value::Value;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// An error reply sent by Chrome in response to a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProtocolError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<String>,
+    /// The method of the message Chrome replied to
+    pub method: String,
+}
+
+impl ProtocolError {
+    /// Builds the error from the `error` object of a reply to `method`
+    pub fn from_value(method: String, error: &Value) -> ProtocolError {
+        ProtocolError {
+            code: error["code"].as_i64().unwrap_or(0),
+            message: error["message"].as_str().unwrap_or("").to_string(),
+            data: error["data"].as_str().map(String::from),
+            method: method,
+        }
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Protocol error ({}): {}", self.method, self.message)?;
+        if let Some(data) = &self.data {
+            write!(f, " {}", data)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ProtocolError {}
+
+/// An exception thrown by JavaScript evaluated in a page
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsException {
+    /// The exception, e.g. `TypeError: x is undefined` or the thrown value
+    pub message: String,
+    /// The frames of the stack trace, innermost first
+    pub stack_trace: Vec<CallFrame>,
+}
+
+impl JsException {
+    pub fn from_details(details: &ExceptionDetails) -> JsException {
+        let message = match &details.exception {
+            // The description of an `Error` is followed by its stack, which
+            // is in `stack_trace` already
+            Some(exception) => match (&exception.description, &exception.value) {
+                (Some(description), _) => description.lines().next().unwrap_or("").to_string(),
+                (None, Some(Value::String(value))) => value.clone(),
+                (None, Some(value)) => value.to_string(),
+                (None, None) => details.text.clone(),
+            },
+            None => details.text.clone(),
+        };
+        let stack_trace = details
+            .stack_trace
+            .as_ref()
+            .map(|stack_trace| stack_trace.call_frames.clone())
+            .unwrap_or_default();
+
+        JsException {
+            message: message,
+            stack_trace: stack_trace,
+        }
+    }
+}
+
+impl fmt::Display for JsException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Evaluation failed: {}", self.message)?;
+        for frame in &self.stack_trace {
+            let function_name = match frame.function_name.as_str() {
+                "" => "<anonymous>",
+                function_name => function_name,
+            };
+            let url = match frame.url.as_str() {
+                "" => "<anonymous>",
+                url => url,
+            };
+            // Line and column numbers are 0-based in the protocol
+            write!(
+                f,
+                "\n    at {} ({}:{}:{})",
+                function_name,
+                url,
+                frame.line_number + 1,
+                frame.column_number + 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for JsException {}
+
+/// The error type of every fallible operation in the crate
+#[derive(Debug)]
+pub enum Error {
+    /// The browser could not be found or started
+    Launch(String),
+    /// A revision of chromium could not be downloaded or extracted
+    Download(String),
+    /// An operation did not complete within its timeout
+    Timeout(String),
+    /// The transport could not be opened, or closed before Chrome replied
+    Connection(String),
+    /// Chrome replied to a message with an error
+    Protocol(ProtocolError),
+    /// A navigation was rejected by the browser, e.g. `net::ERR_NAME_NOT_RESOLVED`
+    Navigation(String),
+    /// The target a message was sent to has been closed
+    TargetClosed,
+    /// Evaluated JavaScript threw an exception or rejected
+    JavaScript(JsException),
+    /// The operation cannot be applied to its arguments, e.g. closing the
+    /// default browser context
+    Unsupported(String),
+    Io(io::Error),
+    /// A reply or event did not match the type it was parsed as
+    Json(serde_json::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Launch(message) => write!(f, "Failed to launch the browser: {}", message),
+            Error::Download(message) => write!(f, "Failed to download chromium: {}", message),
+            Error::Timeout(message) => write!(f, "Timeout: {}", message),
+            Error::Connection(message) => write!(f, "Connection error: {}", message),
+            Error::Protocol(error) => write!(f, "{}", error),
+            Error::Navigation(message) => write!(f, "Navigation failed: {}", message),
+            Error::TargetClosed => write!(f, "Target closed"),
+            Error::JavaScript(exception) => write!(f, "{}", exception),
+            Error::Unsupported(message) => write!(f, "Unsupported operation: {}", message),
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Json(error) => write!(f, "Unexpected JSON: {}", error),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Protocol(error) => Some(error),
+            Error::JavaScript(exception) => Some(exception),
+            Error::Io(error) => Some(error),
+            Error::Json(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<ProtocolError> for Error {
+    fn from(error: ProtocolError) -> Error {
+        Error::Protocol(error)
+    }
+}
+
+impl From<JsException> for Error {
+    fn from(exception: JsException) -> Error {
+        Error::JavaScript(exception)
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Error {
+        Error::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Error {
+        Error::Json(error)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::error::{JsException, ProtocolError};
+    use crate::protocol::runtime::ExceptionDetails;
+
+    #[test]
+    fn test_protocol_error_display() {
+        let _ = env_logger::try_init();
+
+        let error = ProtocolError::from_value(
+            "DOM.describeNode".to_string(),
+            &json!({ "code": -32000, "message": "No node with given id found" }),
+        );
+        assert_eq!(error.code, -32000);
+        assert_eq!(error.data, None);
+        assert_eq!(
+            format!("{}", error),
+            "Protocol error (DOM.describeNode): No node with given id found",
+        );
+
+        let error = ProtocolError::from_value(
+            "Page.navigate".to_string(),
+            &json!({ "code": -32602, "message": "Invalid parameters", "data": "url: string value expected" }),
+        );
+        assert_eq!(
+            format!("{}", error),
+            "Protocol error (Page.navigate): Invalid parameters url: string value expected",
+        );
+    }
+
+    #[test]
+    fn test_js_exception_display() {
+        let _ = env_logger::try_init();
+
+        let details: ExceptionDetails = serde_json::from_value(json!({
+            "exceptionId": 1,
+            "text": "Uncaught",
+            "lineNumber": 0,
+            "columnNumber": 27,
+            "stackTrace": {
+                "callFrames": [
+                    {
+                        "functionName": "fail",
+                        "scriptId": "7",
+                        "url": "",
+                        "lineNumber": 0,
+                        "columnNumber": 27,
+                    },
+                    {
+                        "functionName": "",
+                        "scriptId": "7",
+                        "url": "https://example.com/app.js",
+                        "lineNumber": 9,
+                        "columnNumber": 4,
+                    },
+                ]
+            },
+            "exception": {
+                "type": "object",
+                "subtype": "error",
+                "className": "Error",
+                "description": "Error: boom\n    at fail (<anonymous>:1:28)",
+            }
+        }))
+        .unwrap();
+        let exception = JsException::from_details(&details);
+        assert_eq!(exception.message, "Error: boom");
+        assert_eq!(exception.stack_trace.len(), 2);
+        assert_eq!(
+            format!("{}", exception),
+            "Evaluation failed: Error: boom\n    at fail (<anonymous>:1:28)\n    at \
+             <anonymous> (https://example.com/app.js:10:5)",
+        );
+
+        // A thrown value that is not an `Error` has no description
+        let details: ExceptionDetails = serde_json::from_value(json!({
+            "exceptionId": 2,
+            "text": "Uncaught",
+            "lineNumber": 0,
+            "columnNumber": 0,
+            "exception": { "type": "string", "value": "boom" }
+        }))
+        .unwrap();
+        let exception = JsException::from_details(&details);
+        assert_eq!(format!("{}", exception), "Evaluation failed: boom");
+    }
+}
@@ -0,0 +1,367 @@
+//! Downloads revisions of chromium at runtime
+//!
+//! Revisions are extracted to `<cache dir>/<platform>-<revision>`, which is
+//! where the `Launcher` looks for the revision set by
+//! `PUPPETEER_CHROMIUM_REVISION`.
+
+use crate::chromium::{self, PLATFORM};
+use crate::error::Error;
+use crate::launcher;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+pub use crate::chromium::DEFAULT_DOWNLOAD_HOST;
+
+#[derive(Debug)]
+pub struct BrowserFetcherOptions {
+    /// The cache directory, `Launcher::cache_dir` if `None`
+    pub path: Option<PathBuf>,
+    /// The host to download from, such as a mirror of the default one
+    pub host: String,
+}
+
+impl BrowserFetcherOptions {
+    pub fn new() -> BrowserFetcherOptions {
+        BrowserFetcherOptions {
+            path: None,
+            host: DEFAULT_DOWNLOAD_HOST.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RevisionInfo {
+    pub revision: String,
+    /// The directory the revision is extracted to
+    pub folder_path: PathBuf,
+    pub executable_path: PathBuf,
+    /// The URL the revision is downloaded from
+    pub url: String,
+    /// Whether the revision has been downloaded
+    pub local: bool,
+}
+
+pub struct BrowserFetcher {
+    cache_dir: PathBuf,
+    host: String,
+}
+
+impl BrowserFetcher {
+    pub fn new(cache_dir: PathBuf, host: String) -> BrowserFetcher {
+        BrowserFetcher {
+            cache_dir: cache_dir,
+            host: host,
+        }
+    }
+
+    /// Checks whether the download host has an archive of `revision`
+    pub fn can_download(&self, revision: &str) -> Result<bool, Error> {
+        let url = self.download_url(revision);
+        let response = reqwest::Client::new()
+            .head(&url)
+            .send()
+            .map_err(|e| Error::Download(format!("Failed to reach {}: {}", url, e)))?;
+        Ok(response.status().is_success())
+    }
+
+    /// Downloads and extracts `revision`, unless it is already downloaded
+    ///
+    /// If `sha256` is given, the archive is only extracted if its SHA-256
+    /// matches. `progress` is called with the number of bytes downloaded so
+    /// far and the size of the archive, which is `0` if the host did not tell.
+    pub fn download<F: FnMut(u64, u64)>(
+        &self,
+        revision: &str,
+        sha256: Option<&str>,
+        mut progress: F,
+    ) -> Result<RevisionInfo, Error> {
+        let revision_info = self.revision_info(revision);
+        if revision_info.local {
+            return Ok(revision_info);
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        let zip_path = self
+            .cache_dir
+            .join(format!("download-{}-{}.zip", PLATFORM, revision));
+        // The revision is extracted next to its folder and renamed once
+        // complete, so that a killed download never leaves a folder behind
+        // that would count as local
+        let extract_path = extract_path(&revision_info.folder_path);
+        info!("Downloading {} to {}", revision_info.url, zip_path.display());
+        let result = fetch(&revision_info.url, &zip_path, &mut progress)
+            .and_then(|checksum| verify(&revision_info.url, &checksum, sha256))
+            .and_then(|_| {
+                if extract_path.exists() {
+                    fs::remove_dir_all(&extract_path)?;
+                }
+                extract_archive(&zip_path, &extract_path)
+            })
+            .and_then(|_| {
+                if revision_info.folder_path.exists() {
+                    fs::remove_dir_all(&revision_info.folder_path)?;
+                }
+                Ok(fs::rename(&extract_path, &revision_info.folder_path)?)
+            });
+        let _ = fs::remove_file(&zip_path);
+        if let Err(error) = result {
+            let _ = fs::remove_dir_all(&extract_path);
+            return Err(error);
+        }
+
+        Ok(self.revision_info(revision))
+    }
+
+    /// Returns the revisions for this platform in the cache directory
+    pub fn local_revisions(&self) -> Result<Vec<String>, Error> {
+        if !self.cache_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let prefix = format!("{}-", PLATFORM);
+        let mut revisions = Vec::new();
+        for entry in fs::read_dir(&self.cache_dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if let Some(name) = entry.file_name().to_str() {
+                // Skip the extractions of downloads in progress or killed
+                if name.starts_with(&prefix) && !name.ends_with(".tmp") {
+                    revisions.push(name[prefix.len()..].to_string());
+                }
+            }
+        }
+        revisions.sort();
+
+        Ok(revisions)
+    }
+
+    /// Deletes a downloaded revision
+    pub fn remove(&self, revision: &str) -> Result<(), Error> {
+        let folder_path = launcher::revision_folder_path(&self.cache_dir, revision);
+        if !folder_path.exists() {
+            return Err(Error::Download(format!(
+                "Revision {} is not downloaded to {}",
+                revision,
+                self.cache_dir.display()
+            )));
+        }
+        fs::remove_dir_all(&folder_path)?;
+        Ok(())
+    }
+
+    pub fn revision_info(&self, revision: &str) -> RevisionInfo {
+        let executable_path = launcher::revision_executable_path(&self.cache_dir, revision);
+
+        RevisionInfo {
+            revision: revision.to_string(),
+            folder_path: launcher::revision_folder_path(&self.cache_dir, revision),
+            local: executable_path.is_file(),
+            executable_path: executable_path,
+            url: self.download_url(revision),
+        }
+    }
+
+    fn download_url(&self, revision: &str) -> String {
+        chromium::download_url(&self.host, revision)
+    }
+}
+
+/// Where a revision is extracted to before it is renamed to `folder_path`
+fn extract_path(folder_path: &Path) -> PathBuf {
+    let mut extract_path = folder_path.as_os_str().to_owned();
+    extract_path.push(".tmp");
+    PathBuf::from(extract_path)
+}
+
+/// Downloads `url` to `path`, reporting the progress as it goes, and returns
+/// the SHA-256 of the download
+fn fetch<F: FnMut(u64, u64)>(url: &str, path: &Path, progress: &mut F) -> Result<String, Error> {
+    let mut response =
+        reqwest::get(url).map_err(|e| Error::Download(format!("Failed to reach {}: {}", url, e)))?;
+    if !response.status().is_success() {
+        return Err(Error::Download(format!(
+            "{} responded with {}",
+            url,
+            response.status()
+        )));
+    }
+
+    let total = response.content_length().unwrap_or(0);
+    let mut downloaded = 0;
+    let mut file = File::create(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 64 * 1024];
+    loop {
+        let read = response.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.input(&buffer[..read]);
+        file.write_all(&buffer[..read])?;
+        downloaded += read as u64;
+        progress(downloaded, total);
+    }
+
+    Ok(format!("{:x}", hasher.result()))
+}
+
+/// Fails if `checksum` is not the `expected` one, if any
+fn verify(url: &str, checksum: &str, expected: Option<&str>) -> Result<(), Error> {
+    match expected {
+        Some(expected) if !expected.eq_ignore_ascii_case(checksum) => Err(Error::Download(format!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            url, expected, checksum
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn extract_archive(zip_path: &Path, folder_path: &Path) -> Result<(), Error> {
+    let invalid_archive =
+        |e: zip::result::ZipError| Error::Download(format!("Invalid archive: {}", e));
+    let mut archive = zip::ZipArchive::new(File::open(zip_path)?).map_err(invalid_archive)?;
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(invalid_archive)?;
+        // Refuse entries such as `../../.bashrc` that would escape the folder
+        let outfile_name = match file.enclosed_name() {
+            Some(name) => folder_path.join(name),
+            None => {
+                return Err(Error::Download(format!(
+                    "Invalid archive: {} is outside of the archive",
+                    file.name()
+                )));
+            }
+        };
+
+        if file.name().ends_with('/') {
+            fs::create_dir_all(&outfile_name)?;
+        } else {
+            if let Some(parent) = outfile_name.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut outfile = File::create(&outfile_name)?;
+            io::copy(&mut file, &mut outfile)?;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            if let Some(mode) = file.unix_mode() {
+                fs::set_permissions(&outfile_name, fs::Permissions::from_mode(mode))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::chromium::{ARCHIVE_DIR, DOWNLOAD_PATH};
+    use crate::fetcher::BrowserFetcher;
+    use crate::launcher::EXECUTABLE;
+    use sha2::{Digest, Sha256};
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Cursor, Write};
+    use std::net::TcpListener;
+    use std::process;
+    use std::thread;
+    use zip::write::{FileOptions, ZipWriter};
+
+    fn fake_archive() -> Vec<u8> {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        archive
+            .start_file(
+                format!("{}/{}", ARCHIVE_DIR, EXECUTABLE),
+                FileOptions::default().unix_permissions(0o755),
+            )
+            .unwrap();
+        archive.write_all(b"#!/bin/sh\n").unwrap();
+        archive.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_browser_fetcher() {
+        let _ = env_logger::try_init();
+
+        let archive = fake_archive();
+        let archive_path = format!("/{}/123456/{}.zip", DOWNLOAD_PATH, ARCHIVE_DIR);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let host = format!("http://{}", listener.local_addr().unwrap());
+
+        // Plays the part of the download host
+        let archive_len = archive.len() as u64;
+        let checksum = format!("{:x}", Sha256::digest(&archive));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                reader.read_line(&mut request_line).unwrap();
+                // Skip the headers
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 2 {
+                    header.clear();
+                }
+
+                let mut parts = request_line.split(' ');
+                let method = parts.next().unwrap();
+                if parts.next() == Some(archive_path.as_str()) {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        archive.len()
+                    )
+                    .unwrap();
+                    if method == "GET" {
+                        stream.write_all(&archive).unwrap();
+                    }
+                } else {
+                    write!(
+                        stream,
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    )
+                    .unwrap();
+                }
+            }
+        });
+
+        let cache_dir = format!("rusty_puppet_fetcher_test-{}", process::id());
+        let cache_dir = env::temp_dir().join(cache_dir);
+        let _ = fs::remove_dir_all(&cache_dir);
+        let fetcher = BrowserFetcher::new(cache_dir.clone(), host);
+
+        assert!(fetcher.can_download("123456").unwrap());
+        assert!(!fetcher.can_download("1").unwrap());
+        assert!(fetcher.download("1", None, |_, _| ()).is_err());
+        assert!(!fetcher.revision_info("123456").local);
+        let mismatch = fetcher.download("123456", Some(&"0".repeat(64)), |_, _| ());
+        assert!(mismatch.is_err());
+        assert!(!fetcher.revision_info("123456").local);
+        assert!(fetcher.local_revisions().unwrap().is_empty());
+
+        let mut progress = Vec::new();
+        let revision_info = fetcher
+            .download("123456", Some(&checksum), |downloaded, total| {
+                progress.push((downloaded, total))
+            })
+            .unwrap();
+        assert!(revision_info.local);
+        assert!(revision_info.executable_path.is_file());
+        assert_eq!(progress.last(), Some(&(archive_len, archive_len)));
+        assert_eq!(fetcher.local_revisions().unwrap(), ["123456"]);
+
+        fetcher.remove("123456").unwrap();
+        assert!(fetcher.local_revisions().unwrap().is_empty());
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
@@ -1,5 +1,8 @@
 use crate::browser::Browser;
-use crate::launcher::{LaunchOptions, Launcher};
+use crate::error::Error;
+#[cfg(feature = "fetcher")]
+use crate::fetcher::{BrowserFetcher, BrowserFetcherOptions};
+use crate::launcher::{ConnectOptions, LaunchOptions, Launcher};
 
 pub struct Handle {
     project_root: Option<String>,
@@ -21,38 +24,56 @@ impl Handle {
         }
     }
 
-    pub async fn launch<'a>(&'a self) -> Browser {
+    pub async fn launch<'a>(&'a self) -> Result<Browser, Error> {
         let options = LaunchOptions::new();
         await!(self.launcher.launch(&options))
     }
 
-    pub async fn launch_with_opts<'a>(&'a self, options: &'a LaunchOptions) -> Browser {
+    pub async fn launch_with_opts<'a>(
+        &'a self,
+        options: &'a LaunchOptions,
+    ) -> Result<Browser, Error> {
         await!(self.launcher.launch(options))
     }
 
-    //pub fn connect(options) {
-    //  return this._launcher.connect(options);
-    //}
+    pub async fn connect<'a>(&'a self, options: &'a ConnectOptions) -> Result<Browser, Error> {
+        await!(self.launcher.connect(options))
+    }
 
-    //pub fn executablePath() {
-    //  return this._launcher.executablePath();
-    //}
+    /// Returns the chrome executable launched when no
+    /// `LaunchOptions::executable_path` is given
+    pub fn executable_path(&self) -> Result<String, Error> {
+        self.launcher.executable_path()
+    }
 
-    //pub fn defaultArgs(options) {
-    //  return this._launcher.defaultArgs(options);
-    //}
+    /// Returns the arguments chrome would be launched with for `options`
+    pub fn default_args(&self, options: &LaunchOptions) -> Vec<String> {
+        Launcher::default_args(options)
+    }
 
-    //pub fn createBrowserFetcher(options) {
-    //  return new BrowserFetcher(this._projectRoot, options);
-    //}
+    /// Creates a fetcher for the cache directory the launcher looks in,
+    /// unless `options.path` is set
+    #[cfg(feature = "fetcher")]
+    pub fn create_browser_fetcher(&self, options: BrowserFetcherOptions) -> BrowserFetcher {
+        let cache_dir = options.path.unwrap_or_else(|| self.launcher.cache_dir());
+        BrowserFetcher::new(cache_dir, options.host)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     extern crate env_logger;
 
+    use crate::error::Error;
     use crate::handle::Handle;
+    use crate::launcher::ConnectOptions;
+    use crate::page::{PageOptions, WaitUntil};
+    use crate::target::Target;
     use futures::executor::block_on;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_construct_no_throw() {
@@ -66,19 +87,246 @@ mod tests {
         let _ = env_logger::try_init();
 
         let handle = Handle::new();
-        let mut browser = block_on(handle.launch());
-        block_on(browser.close());
+        let mut browser = block_on(handle.launch()).unwrap();
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_browser_metadata() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        assert!(block_on(browser.version()).unwrap().contains("Chrome/"));
+        assert!(block_on(browser.user_agent()).unwrap().starts_with("Mozilla/"));
+        assert!(browser.ws_endpoint().unwrap().starts_with("ws://"));
+        assert!(browser.process().is_some());
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_disconnect_reconnect() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        let pid = browser.process().unwrap();
+        let disconnected = browser.disconnected();
+        browser.disconnect();
+        block_on(disconnected);
+        // The browser keeps running and we keep its process
+        assert_eq!(browser.process(), Some(pid));
+
+        block_on(browser.reconnect()).unwrap();
+        block_on(browser.new_page()).unwrap();
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_close_disconnected() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        browser.disconnect();
+        // Asked to close rather than killed after the grace period
+        let start = Instant::now();
+        block_on(browser.close()).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn test_new_page() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        let page = block_on(browser.new_page()).unwrap();
+        assert!(!page.target_id().is_empty());
+        assert!(!page.session_id().is_empty());
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_targets() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        let page = block_on(browser.new_page()).unwrap();
+        let targets = browser.targets();
+        assert!(targets.iter().any(|target| target.target_id() == page.target_id()));
+        // The initial tab and the new one
+        let pages = block_on(browser.pages()).unwrap();
+        assert_eq!(pages.len(), 2);
+        // Tabs keep their session across calls
+        let new_tab = pages.iter().find(|p| p.target_id() == page.target_id()).unwrap();
+        assert_eq!(new_tab.session_id(), page.session_id());
+        let session_ids: Vec<_> = pages.iter().map(|page| page.session_id().to_string()).collect();
+        let pages = block_on(browser.pages()).unwrap();
+        assert!(pages.iter().all(|page| session_ids.contains(&page.session_id().to_string())));
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_incognito_browser_context() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        {
+            let context = block_on(browser.create_incognito_browser_context()).unwrap();
+            assert_eq!(browser.browser_contexts().len(), 2);
+
+            let page = block_on(context.new_page()).unwrap();
+            let in_context = |targets: Vec<Target>| {
+                targets
+                    .iter()
+                    .any(|target| target.target_id() == page.target_id())
+            };
+            assert!(in_context(context.targets()));
+            assert!(!in_context(browser.default_browser_context().targets()));
+
+            // Another client sees the context too
+            let mut options = ConnectOptions::new();
+            options.browser_ws_endpoint = browser.ws_endpoint().map(String::from);
+            let mut other = block_on(handle.connect(&options)).unwrap();
+            assert_eq!(other.browser_contexts().len(), 2);
+            assert!(!in_context(other.default_browser_context().targets()));
+            other.disconnect();
+
+            block_on(context.close()).unwrap();
+            assert_eq!(browser.browser_contexts().len(), 1);
+            match block_on(browser.default_browser_context().close()) {
+                Err(Error::Unsupported(_)) => (),
+                other => panic!("Expected an unsupported operation, got {:?}", other),
+            }
+        }
+        block_on(browser.close()).unwrap();
+    }
+
+    #[test]
+    fn test_page_evaluate() {
+        let _ = env_logger::try_init();
+
+        let handle = Handle::new();
+        let mut browser = block_on(handle.launch()).unwrap();
+        let page = block_on(browser.new_page()).unwrap();
+        let sum: i64 = block_on(page.evaluate("1 + 2", Vec::new())).unwrap();
+        assert_eq!(sum, 3);
+        let args = vec![json!(2), json!([3, 4])];
+        let product: Vec<i64> =
+            block_on(page.evaluate("async (a, b) => b.map(x => x * a)", args)).unwrap();
+        assert_eq!(product, [6, 8]);
+        match block_on(page.evaluate::<i64>("1 + 2", vec![json!(1)])) {
+            Err(Error::Unsupported(_)) => (),
+            other => panic!("Expected an unsupported operation, got {:?}", other),
+        }
+
+        match block_on(page.evaluate::<()>("() => { throw new Error('boom'); }", Vec::new())) {
+            Err(Error::JavaScript(exception)) => {
+                assert_eq!(exception.message, "Error: boom");
+                assert!(!exception.stack_trace.is_empty());
+            }
+            other => panic!("Expected a JavaScript exception, got {:?}", other),
+        }
+
+        let window = block_on(page.evaluate_handle("window", Vec::new())).unwrap();
+        assert!(window.remote_object().object_id.is_some());
+        block_on(window.dispose()).unwrap();
+        let location = block_on(page.evaluate_handle("location.href", Vec::new())).unwrap();
+        let href: String = block_on(location.json_value()).unwrap();
+        assert_eq!(href, "about:blank");
+        block_on(browser.close()).unwrap();
+    }
+
+    /// Serves a page at every path but `/missing`, which is not found, and
+    /// `/slow`, which never gets a reply
+    fn serve_pages() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        thread::spawn(move || {
+            // Keeps the connections of `/slow` open
+            let mut slow_streams = Vec::new();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut header = String::new();
+                while reader.read_line(&mut header).unwrap() > 0 && header.trim() != "" {
+                    header.clear();
+                }
+
+                let body = "<html><body>Hello</body></html>";
+                let status = match request_line.split_whitespace().nth(1) {
+                    Some("/slow") => {
+                        slow_streams.push(stream);
+                        continue;
+                    }
+                    Some("/missing") => "404 Not Found",
+                    _ => "200 OK",
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: text/html\r\nX-Test: served\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        format!("http://{}", address)
     }
 
     #[test]
-    #[ignore]
     fn test_page_goto() {
         let _ = env_logger::try_init();
 
+        let server = serve_pages();
         let handle = Handle::new();
-        let mut browser = block_on(handle.launch());
-        let page = browser.new_page();
-        page.goto("https://example.com".to_string());
-        block_on(browser.close());
+        let mut browser = block_on(handle.launch()).unwrap();
+        let page = block_on(browser.new_page()).unwrap();
+
+        let wait_untils = [
+            WaitUntil::Load,
+            WaitUntil::DomContentLoaded,
+            WaitUntil::NetworkIdle0,
+            WaitUntil::NetworkIdle2,
+        ];
+        for (i, &wait_until) in wait_untils.iter().enumerate() {
+            let url = format!("{}/page?{}", server, i);
+            let mut options = PageOptions::new();
+            options.wait_until = wait_until;
+            let response = block_on(page.goto_with_options(url.clone(), options))
+                .unwrap()
+                .unwrap();
+            assert_eq!(response.url, url);
+            assert_eq!(response.status, 200);
+            assert!(response.ok());
+            let header = response
+                .headers
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("x-test"))
+                .map(|(_, value)| value.as_str());
+            assert_eq!(header, Some("served"));
+        }
+
+        let missing = block_on(page.goto(format!("{}/missing", server)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(missing.status, 404);
+        assert!(!missing.ok());
+
+        let mut options = PageOptions::new();
+        options.timeout = 500;
+        match block_on(page.goto_with_options(format!("{}/slow", server), options)) {
+            Err(Error::Timeout(_)) => (),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+        block_on(browser.close()).unwrap();
     }
 }
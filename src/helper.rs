@@ -0,0 +1,289 @@
+//! Small utilities shared by the page and browser implementations
+
+use crate::error::Error;
+use futures::channel::oneshot;
+use futures::future::{self, Either};
+use std::future::Future;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::collections::BTreeMap;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use websocket::url::Url;
+
+lazy_static! {
+    static ref TIMER: Timer = Timer {
+        state: Mutex::new(TimerState {
+            started: false,
+            next_id: 0,
+            pending: BTreeMap::new(),
+        }),
+        changed: Condvar::new(),
+    };
+}
+
+/// Identifies a pending timer by its deadline and a sequence number, which
+/// keeps timers with the same deadline apart
+type TimerKey = (Instant, u64);
+
+/// The timers of `sleep`, all served by one thread
+struct Timer {
+    state: Mutex<TimerState>,
+    /// Notified when a timer is added, as it may be due before the others
+    changed: Condvar,
+}
+
+struct TimerState {
+    started: bool,
+    next_id: u64,
+    pending: BTreeMap<TimerKey, oneshot::Sender<()>>,
+}
+
+impl Timer {
+    /// Adds a timer, starting the timer thread with the first one
+    fn schedule(
+        &'static self,
+        deadline: Instant,
+    ) -> Result<(TimerKey, oneshot::Receiver<()>), Error> {
+        let mut state = self.state.lock().unwrap();
+        if !state.started {
+            thread::Builder::new()
+                .name("timer".to_string())
+                .spawn(move || self.run())?;
+            state.started = true;
+        }
+
+        let (sender, receiver) = oneshot::channel();
+        let key = (deadline, state.next_id);
+        state.next_id += 1;
+        state.pending.insert(key, sender);
+        self.changed.notify_one();
+        Ok((key, receiver))
+    }
+
+    fn cancel(&self, key: &TimerKey) {
+        self.state.lock().unwrap().pending.remove(key);
+    }
+
+    /// Fires the timers as they become due
+    fn run(&self) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            while let Some(&key) = state.pending.keys().next() {
+                if key.0 > now {
+                    break;
+                }
+                if let Some(sender) = state.pending.remove(&key) {
+                    let _ = sender.send(());
+                }
+            }
+
+            state = match state.pending.keys().next() {
+                Some(&(deadline, _)) => self.changed.wait_timeout(state, deadline - now).unwrap().0,
+                None => self.changed.wait(state).unwrap(),
+            };
+        }
+    }
+}
+
+/// Removes a timer that has not fired when its `sleep` is dropped
+struct CancelOnDrop(TimerKey);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        TIMER.cancel(&self.0);
+    }
+}
+
+/// Resolves after `duration` milliseconds
+///
+/// The timers of all sleeps are kept by a single thread, so unlike
+/// `thread::sleep` this does not hold up the executor or the transport
+/// threads. Dropping the future cancels its timer. Fails only if the timer
+/// thread cannot be started.
+pub async fn sleep(duration: u32) -> Result<(), Error> {
+    let deadline = Instant::now() + Duration::from_millis(u64::from(duration));
+    let (key, receiver) = TIMER.schedule(deadline)?;
+    let _cancel_on_drop = CancelOnDrop(key);
+
+    let _ = await!(receiver);
+    Ok(())
+}
+
+/// Resolves to the output of `future`, or to `None` if it takes longer than
+/// `timeout` milliseconds. A timeout of `0` waits indefinitely.
+pub async fn timeout<F: Future>(future: F, timeout: u32) -> Result<Option<F::Output>, Error> {
+    if timeout == 0 {
+        return Ok(Some(await!(future)));
+    }
+
+    match await!(future::select(Box::pin(future), Box::pin(sleep(timeout)))) {
+        Either::Left((output, _)) => Ok(Some(output)),
+        Either::Right((slept, _)) => slept.map(|_| None),
+    }
+}
+
+/// Fetches `url` with a plain HTTP GET and returns the body
+///
+/// Only meant for the small JSON documents served by the DevTools HTTP
+/// handler, such as `/json/version`, so chunked replies are not supported.
+/// Connecting and each read give up after `timeout` milliseconds, unless it
+/// is `0`.
+pub fn http_get(url: &Url, timeout: u32) -> Result<String, Error> {
+    if url.scheme() != "http" {
+        return Err(Error::Connection(format!("Unsupported scheme in {}", url)));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::Connection(format!("No host in {}", url)))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let timeout = match timeout {
+        0 => None,
+        timeout => Some(Duration::from_millis(u64::from(timeout))),
+    };
+
+    let connection_error =
+        |e: io::Error| Error::Connection(format!("Failed to connect to {}: {}", url, e));
+    // IPv6 hosts are bracketed in URLs, but not in socket addresses
+    let addresses = (host.trim_start_matches('[').trim_end_matches(']'), port)
+        .to_socket_addrs()
+        .map_err(connection_error)?;
+    let mut stream = None;
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "No address found");
+    for address in addresses {
+        let connected = match timeout {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout),
+            None => TcpStream::connect(address),
+        };
+        match connected {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(e) => last_error = e,
+        }
+    }
+    let mut stream = stream.ok_or_else(|| connection_error(last_error))?;
+    stream.set_read_timeout(timeout)?;
+
+    let path = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    };
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: {}:{}\r\nConnection: close\r\n\r\n",
+        path, host, port
+    )?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status_line = String::new();
+    reader.read_line(&mut status_line)?;
+    if status_line.split_whitespace().nth(1) != Some("200") {
+        return Err(Error::Connection(format!(
+            "GET {} failed: {}",
+            url,
+            status_line.trim()
+        )));
+    }
+
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value.parse::<usize>().ok();
+        } else if name.eq_ignore_ascii_case("transfer-encoding")
+            && !value.eq_ignore_ascii_case("identity")
+        {
+            return Err(Error::Connection(format!(
+                "GET {} replied with unsupported Transfer-Encoding: {}",
+                url, value
+            )));
+        }
+    }
+
+    let mut body = Vec::new();
+    match content_length {
+        Some(length) => {
+            body.resize(length, 0);
+            reader.read_exact(&mut body)?;
+        }
+        None => {
+            reader.read_to_end(&mut body)?;
+        }
+    }
+
+    String::from_utf8(body)
+        .map_err(|e| Error::Connection(format!("GET {} returned invalid UTF-8: {}", url, e)))
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::helper;
+    use futures::executor::block_on;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use websocket::url::Url;
+
+    #[test]
+    fn test_timeout() {
+        let _ = env_logger::try_init();
+
+        let output = block_on(helper::timeout(helper::sleep(10), 1000)).unwrap();
+        assert!(output.unwrap().is_ok());
+        assert!(block_on(helper::timeout(helper::sleep(1000), 10))
+            .unwrap()
+            .is_none());
+        assert!(block_on(helper::timeout(helper::sleep(10), 0)).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_http_get() {
+        let _ = env_logger::try_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let url = Url::parse(&format!("http://{}/json/version", address)).unwrap();
+
+        let server = thread::spawn(move || {
+            let mut streams = Vec::new();
+            for (i, stream) in listener.incoming().take(3).enumerate() {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&mut stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                match i {
+                    0 => write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\n{{}}"),
+                    1 => write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                         2\r\n{{}}\r\n0\r\n\r\n"
+                    ),
+                    // Never replies, but keeps the connection open
+                    _ => Ok(()),
+                }
+                .unwrap();
+                streams.push(stream);
+            }
+            // Keeps the connections open until joined
+            streams
+        });
+
+        assert_eq!(helper::http_get(&url, 1000).unwrap(), "{}");
+        assert!(helper::http_get(&url, 1000).is_err());
+        assert!(helper::http_get(&url, 200).is_err());
+        server.join().unwrap();
+    }
+}
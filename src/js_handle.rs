@@ -0,0 +1,133 @@
+use crate::error::{Error, JsException};
+use crate::protocol::runtime::{
+    CallFunctionOn, ExceptionDetails, ReleaseObject, RemoteObject, RemoteObjectType,
+};
+use crate::session::CdpSession;
+use serde::de::{self, DeserializeOwned};
+use serde_json::value::Value;
+
+/// A reference to a JavaScript value in a page
+///
+/// Unlike the results of `Page::evaluate`, the value stays in the page, so it
+/// need not be serializable, e.g. a DOM node, a function or `window`. The page
+/// holds on to objects until their handle is disposed or the page navigates.
+pub struct JsHandle {
+    session: CdpSession,
+    remote_object: RemoteObject,
+}
+
+impl JsHandle {
+    pub fn new(session: CdpSession, remote_object: RemoteObject) -> JsHandle {
+        JsHandle {
+            session: session,
+            remote_object: remote_object,
+        }
+    }
+
+    pub fn remote_object(&self) -> &RemoteObject {
+        &self.remote_object
+    }
+
+    /// Deserializes the value from its JSON representation in the page
+    ///
+    /// Fails with `Error::JavaScript` for values that cannot be serialized to
+    /// JSON, such as objects with cycles.
+    pub async fn json_value<T: DeserializeOwned>(&self) -> Result<T, Error> {
+        let object_id = match &self.remote_object.object_id {
+            Some(object_id) => object_id.clone(),
+            // Primitives are sent by value already
+            None => return from_remote_object(&self.remote_object),
+        };
+
+        let mut call_function_on = CallFunctionOn::new("function() { return this; }".to_string());
+        call_function_on.object_id = Some(object_id);
+        call_function_on.return_by_value = Some(true);
+        call_function_on.await_promise = Some(true);
+        let result = await!(self.session.send(call_function_on))?;
+        check_exception(result.exception_details)?;
+        from_remote_object(&result.result)
+    }
+
+    /// Lets the page release the object
+    pub async fn dispose(self) -> Result<(), Error> {
+        if let Some(object_id) = self.remote_object.object_id {
+            await!(self.session.send(ReleaseObject::new(object_id)))?;
+        }
+        Ok(())
+    }
+}
+
+/// Fails with the exception of an evaluation, if any
+pub(crate) fn check_exception(exception_details: Option<ExceptionDetails>) -> Result<(), Error> {
+    match exception_details {
+        Some(exception_details) => Err(JsException::from_details(&exception_details).into()),
+        None => Ok(()),
+    }
+}
+
+/// Deserializes a value sent with `returnByValue`
+///
+/// `undefined` is deserialized from `null`, so that it fits `()` and `Option`.
+pub(crate) fn from_remote_object<T: DeserializeOwned>(
+    remote_object: &RemoteObject,
+) -> Result<T, Error> {
+    if let Some(unserializable_value) = &remote_object.unserializable_value {
+        // Such as `NaN`, `-0` or a bigint
+        let error = <serde_json::Error as de::Error>::custom(format!(
+            "{} cannot be represented in JSON",
+            unserializable_value
+        ));
+        return Err(error.into());
+    }
+
+    let value = match &remote_object.value {
+        Some(value) => value.clone(),
+        None if remote_object.r#type == RemoteObjectType::Undefined => Value::Null,
+        None => {
+            let error = <serde_json::Error as de::Error>::custom(format!(
+                "{} was not sent by value",
+                remote_object.description.as_ref().map_or("The result", String::as_str)
+            ));
+            return Err(error.into());
+        }
+    };
+    Ok(serde_json::from_value(value)?)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::error::Error;
+    use crate::js_handle;
+    use crate::protocol::runtime::{RemoteObject, RemoteObjectSubtype};
+
+    fn remote_object(value: serde_json::Value) -> RemoteObject {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_from_remote_object() {
+        let _ = env_logger::try_init();
+
+        let object = remote_object(json!({ "type": "object", "value": { "a": [1, 2] } }));
+        let value: serde_json::Value = js_handle::from_remote_object(&object).unwrap();
+        assert_eq!(value, json!({ "a": [1, 2] }));
+        // Subtypes added by newer versions of Chrome don't fail the reply
+        let object = remote_object(json!({ "type": "object", "subtype": "newtype", "value": {} }));
+        assert_eq!(object.subtype, Some(RemoteObjectSubtype::Unknown));
+        let number = remote_object(json!({ "type": "number", "value": 42 }));
+        assert_eq!(js_handle::from_remote_object::<u32>(&number).unwrap(), 42);
+
+        let undefined = remote_object(json!({ "type": "undefined" }));
+        js_handle::from_remote_object::<()>(&undefined).unwrap();
+        let undefined = js_handle::from_remote_object::<Option<String>>(&undefined).unwrap();
+        assert_eq!(undefined, None);
+
+        let nan = remote_object(json!({ "type": "number", "unserializableValue": "NaN" }));
+        match js_handle::from_remote_object::<f64>(&nan) {
+            Err(Error::Json(_)) => (),
+            other => panic!("Expected a JSON error, got {:?}", other),
+        }
+    }
+}
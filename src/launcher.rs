@@ -1,16 +1,45 @@
 use crate::browser::Browser;
+use crate::chromium::{ARCHIVE_DIR, PLATFORM};
 use crate::connection::Connection;
-use crate::ws::{Response, WebSocketTransport};
+use crate::error::Error;
+use crate::helper;
+use crate::pipe;
+use crate::target::TargetType;
+#[cfg(unix)]
+use crate::signals;
 use rand;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use regex::Regex;
+use serde_json::value::Value;
 use std::collections::HashMap;
 use std::env;
+use std::ffi::OsStr;
 use std::fs;
-use std::io::{BufRead, BufReader};
-use std::path::Path;
-use std::process::{Child, ChildStderr, Command, Stdio};
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+use websocket::url::Url;
+
+/// The chromium revision used unless `PUPPETEER_CHROMIUM_REVISION` is set
+pub const DEFAULT_REVISION: &'static str = env!("CHROMIUM_REVISION");
+
+/// The path of the executable within `ARCHIVE_DIR`
+#[cfg(target_os = "linux")]
+pub(crate) const EXECUTABLE: &'static str = "chrome";
+#[cfg(target_os = "macos")]
+pub(crate) const EXECUTABLE: &'static str = "Chromium.app/Contents/MacOS/Chromium";
+#[cfg(target_os = "windows")]
+pub(crate) const EXECUTABLE: &'static str = "chrome.exe";
+
+/// The names chrome is installed under on the `PATH`
+const SYSTEM_EXECUTABLES: [&'static str; 3] = ["google-chrome", "chromium", "chromium-browser"];
+
+/// The log target the output of chrome is forwarded to with `dumpio`
+const BROWSER_STDIO_TARGET: &'static str = "rusty_puppet::browser_stdio";
 
 const DEFAULT_ARGS: [&'static str; 22] = [
     "--disable-background-networking",
@@ -38,7 +67,7 @@ const DEFAULT_ARGS: [&'static str; 22] = [
     "--use-mock-keychain",
 ];
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Viewport {
     pub width: i32,
     pub height: i32,
@@ -48,21 +77,45 @@ pub struct Viewport {
     pub isLandscape: bool,
 }
 
+/// Which of the default arguments not to pass to chrome
+#[derive(Debug, Clone, PartialEq)]
+pub enum IgnoreDefaultArgs {
+    /// Pass all of them
+    Nothing,
+    /// Pass none of them, only `LaunchOptions::args`
+    All,
+    /// Pass all but the listed ones
+    Only(Vec<String>),
+}
+
 #[derive(Debug)]
 pub struct LaunchOptions {
     pub ignore_https_errors: bool,
+    /// Run without a window. Ignored when `devtools` is set
     pub headless: bool,
     pub executable_path: Option<String>,
     pub slow_mo: u32,
     pub default_viewport: Option<Viewport>,
+    /// Additional arguments to pass to chrome
     pub args: Vec<String>,
-    pub ignore_default_args: bool,
+    pub ignore_default_args: IgnoreDefaultArgs,
     pub timeout: u32,
+    /// Forward the stdout and stderr of chrome to the `log` crate, under the
+    /// `rusty_puppet::browser_stdio` target
     pub dumpio: bool,
+    /// The profile to use instead of a temporary one, which is left in place
+    /// when the browser closes
     pub user_data_dir: Option<String>,
     pub env: HashMap<String, String>,
+    /// Open the DevTools panel for each tab, which requires a window
     pub devtools: bool,
     pub pipe: bool,
+    /// Kill the browser and remove its temporary profile on SIGINT
+    pub handle_sigint: bool,
+    /// Kill the browser and remove its temporary profile on SIGTERM
+    pub handle_sigterm: bool,
+    /// Kill the browser and remove its temporary profile on SIGHUP
+    pub handle_sighup: bool,
 }
 
 impl LaunchOptions {
@@ -74,13 +127,47 @@ impl LaunchOptions {
             slow_mo: 0,
             default_viewport: None,
             args: Vec::new(),
-            ignore_default_args: false,
+            ignore_default_args: IgnoreDefaultArgs::Nothing,
             timeout: 30000,
             dumpio: false,
             user_data_dir: None,
             env: HashMap::new(),
             devtools: false,
             pipe: false,
+            handle_sigint: false,
+            handle_sigterm: false,
+            handle_sighup: false,
+        }
+    }
+}
+
+/// Options for connecting to a running browser
+///
+/// Exactly one of `browser_ws_endpoint` and `browser_url` must be set.
+#[derive(Debug)]
+pub struct ConnectOptions {
+    /// A `ws://` endpoint as printed by chrome on startup
+    pub browser_ws_endpoint: Option<String>,
+    /// The `http://` address of the DevTools HTTP handler, e.g.
+    /// `http://127.0.0.1:9222`, which is asked for the endpoint
+    pub browser_url: Option<String>,
+    pub ignore_https_errors: bool,
+    pub default_viewport: Option<Viewport>,
+    pub slow_mo: u32,
+    /// Maximum time in milliseconds to wait for the DevTools HTTP handler to
+    /// connect and to send each part of its reply, `0` disables the timeout
+    pub timeout: u32,
+}
+
+impl ConnectOptions {
+    pub fn new() -> ConnectOptions {
+        ConnectOptions {
+            browser_ws_endpoint: None,
+            browser_url: None,
+            ignore_https_errors: false,
+            default_viewport: None,
+            slow_mo: 0,
+            timeout: 30000,
         }
     }
 }
@@ -98,6 +185,26 @@ fn has(needle: &str, haystack: &Vec<String>) -> bool {
     false
 }
 
+/// Returns the directory the archive of `revision` is extracted to in
+/// `cache_dir`
+pub fn revision_folder_path(cache_dir: &Path, revision: &str) -> PathBuf {
+    cache_dir.join(format!("{}-{}", PLATFORM, revision))
+}
+
+/// Returns where the executable of `revision` is extracted to in `cache_dir`
+pub fn revision_executable_path(cache_dir: &Path, revision: &str) -> PathBuf {
+    revision_folder_path(cache_dir, revision)
+        .join(ARCHIVE_DIR)
+        .join(EXECUTABLE)
+}
+
+/// Returns the first executable called `name` in the directories of `path`
+fn find_in_path(name: &str, path: &OsStr) -> Option<PathBuf> {
+    env::split_paths(path)
+        .map(|dir| dir.join(name))
+        .find(|executable_path| executable_path.is_file())
+}
+
 impl Launcher {
     pub fn new() -> Launcher {
         Launcher { project_root: None }
@@ -109,8 +216,15 @@ impl Launcher {
         }
     }
 
-    pub async fn launch<'a>(&'a self, options: &'a LaunchOptions) -> Browser {
-        let mut chrome_arguments = Launcher::initial_arguments();
+    pub async fn launch<'a>(&'a self, options: &'a LaunchOptions) -> Result<Browser, Error> {
+        let mut chrome_arguments = match &options.ignore_default_args {
+            IgnoreDefaultArgs::Nothing => Launcher::default_args(options),
+            IgnoreDefaultArgs::All => options.args.clone(),
+            IgnoreDefaultArgs::Only(ignored) => Launcher::default_args(options)
+                .into_iter()
+                .filter(|arg| !ignored.contains(arg))
+                .collect(),
+        };
 
         // Ensure remote debugging argument is set
         if !has("--remote-debugging", &chrome_arguments) {
@@ -123,139 +237,510 @@ impl Launcher {
             chrome_arguments.push(debug_argument);
         }
 
+        // Get executable
+        let chrome_executable = match options.executable_path.clone() {
+            Some(path) => path,
+            None => {
+                let path = self.executable_path()?;
+                info!("Executable located: {}", &path);
+                path
+            }
+        };
+
         // Ensure user data dir argument is set
-        let mut temporary_user_data_dir;
+        let mut temporary_user_data_dir = None;
         if !has("--user-data-dir", &chrome_arguments) {
             let id: String = rand::thread_rng()
                 .sample_iter(&Alphanumeric)
                 .take(6)
                 .collect();
 
-            temporary_user_data_dir = env::temp_dir();
-            temporary_user_data_dir.push(format!("puppeteer_dev_profile-{}", id));
-            let user_data_dir = temporary_user_data_dir.to_str().unwrap();
-            chrome_arguments.push(format!("--user-data-dir={}", &user_data_dir));
-            fs::create_dir_all(&user_data_dir).unwrap();
+            let user_data_dir = env::temp_dir().join(format!("puppeteer_dev_profile-{}", id));
+            fs::create_dir_all(&user_data_dir)?;
+            chrome_arguments.push(format!("--user-data-dir={}", user_data_dir.display()));
+            temporary_user_data_dir = Some(user_data_dir);
         }
 
-        // Get executable
-        let chrome_executable = options.executable_path.clone().unwrap_or_else(|| {
-            let path = self.resolve_executable_path();
-            info!("Executable located: {}", &path);
-            path
-        });
-
         info!(
             "Running chrome with {:?} {} {}",
             &options.env,
             &chrome_executable,
             &chrome_arguments.join(" ")
         );
-        let mut child = Command::new(&chrome_executable)
-            .args(&chrome_arguments)
-            .envs(&options.env)
-            .stderr(Stdio::piped())
-            .spawn()
-            .expect("failed to execute child");
-
-        let browser_ws_endpoint = Launcher::wait_for_ws_endpoint(
-            child.stderr.take().unwrap(),
-            options.timeout,
-            // this._preferredRevision
-        );
-        let transport = WebSocketTransport::new(browser_ws_endpoint.clone());
-        let connection = Connection::new(browser_ws_endpoint, transport, options.slow_mo);
+        let mut command = Command::new(&chrome_executable);
+        command.args(&chrome_arguments).envs(&options.env);
+        // Whatever is piped is drained for as long as chrome runs, so that it
+        // never blocks on a full pipe
+        if options.dumpio {
+            command.stdout(Stdio::piped());
+        } else {
+            command.stdout(Stdio::null());
+        }
+        let pipes = if options.pipe {
+            if options.dumpio {
+                command.stderr(Stdio::piped());
+            } else {
+                command.stderr(Stdio::null());
+            }
+            match pipe::attach(&mut command) {
+                Ok(pipes) => Some(pipes),
+                Err(error) => {
+                    Launcher::remove_user_data_dir(&temporary_user_data_dir);
+                    return Err(error);
+                }
+            }
+        } else {
+            command.stderr(Stdio::piped());
+            None
+        };
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                Launcher::remove_user_data_dir(&temporary_user_data_dir);
+                return Err(Error::Launch(format!(
+                    "Failed to run {}: {}",
+                    &chrome_executable, e
+                )));
+            }
+        };
+        // Close our copies of the ends of the pipes handed to chrome
+        drop(command);
 
-        let browser = Browser::new(
+        if let Some(stdout) = child.stdout.take() {
+            Launcher::drain_output("stdout", stdout, options.dumpio);
+        }
+        let stderr_lines = child
+            .stderr
+            .take()
+            .map(|stderr| Launcher::drain_output("stderr", stderr, options.dumpio));
+
+        let connection = match pipes {
+            Some((writer, reader)) => Connection::from_pipe(writer, reader, options.slow_mo),
+            None => {
+                let stderr_lines = stderr_lines.expect("stderr of chrome is piped");
+                Launcher::wait_for_ws_endpoint(
+                    stderr_lines,
+                    options.timeout,
+                    // this._preferredRevision
+                )
+                .and_then(|endpoint| Connection::connect(endpoint, options.slow_mo))
+            }
+        };
+        let connection = match connection {
+            Ok(connection) => connection,
+            Err(error) => {
+                // Don't leave a browser behind that nothing is connected to
+                let _ = child.kill();
+                let _ = child.wait();
+                Launcher::remove_user_data_dir(&temporary_user_data_dir);
+                return Err(error);
+            }
+        };
+
+        let pid = child.id();
+        let browser = await!(Browser::new(
             connection,
-            // [],
-            // ignoreHTTPSErrors,
-            // defaultViewport,
-            child,
-            // gracefullyCloseChrome
-        );
+            Some(child),
+            temporary_user_data_dir.clone(),
+            options.ignore_https_errors,
+            options.default_viewport.clone(),
+        ))?;
+
+        #[cfg(unix)]
+        {
+            let mut handled_signals = Vec::new();
+            if options.handle_sigint {
+                handled_signals.push(libc::SIGINT);
+            }
+            if options.handle_sigterm {
+                handled_signals.push(libc::SIGTERM);
+            }
+            if options.handle_sighup {
+                handled_signals.push(libc::SIGHUP);
+            }
+            // On failure the browser is dropped, which kills it
+            signals::register(pid, temporary_user_data_dir, &handled_signals)?;
+        }
 
-        //await!(Launcher::ensure_initial_page(browser));
+        await!(Launcher::ensure_initial_page(&browser, options.timeout))?;
 
-        // TODO Remove temp dir
+        Ok(browser)
+    }
 
-        browser
+    fn remove_user_data_dir(user_data_dir: &Option<PathBuf>) {
+        if let Some(user_data_dir) = user_data_dir {
+            if let Err(e) = fs::remove_dir_all(user_data_dir) {
+                warn!("Failed to remove {}: {}", user_data_dir.display(), e);
+            }
+        }
     }
 
-    fn resolve_executable_path(&self) -> String {
-        let out_dir = env::var("OUT_DIR").unwrap();
-        let chrome_path = Path::new(&out_dir).join("chrome");
+    /// Connects to an already running browser, which is left running when the
+    /// returned `Browser` is dropped
+    pub async fn connect<'a>(&'a self, options: &'a ConnectOptions) -> Result<Browser, Error> {
+        let browser_ws_endpoint = match (&options.browser_ws_endpoint, &options.browser_url) {
+            (Some(browser_ws_endpoint), None) => browser_ws_endpoint.clone(),
+            (None, Some(browser_url)) => {
+                Launcher::resolve_ws_endpoint(browser_url, options.timeout)?
+            }
+            _ => {
+                return Err(Error::Connection(
+                    "Exactly one of browser_ws_endpoint and browser_url must be set".to_string(),
+                ));
+            }
+        };
 
-        if !chrome_path.exists() {
-            panic!("Chromium revision is not downloaded. Run cargo clean and recompile");
+        let connection = Connection::connect(browser_ws_endpoint, options.slow_mo)?;
+        await!(Browser::new(
+            connection,
+            None,
+            None,
+            options.ignore_https_errors,
+            options.default_viewport.clone(),
+        ))
+    }
+
+    /// Asks the DevTools HTTP handler at `browser_url` for the websocket
+    /// endpoint of the browser
+    fn resolve_ws_endpoint(browser_url: &str, timeout: u32) -> Result<String, Error> {
+        let version_url = Url::parse(browser_url)
+            .and_then(|url| url.join("/json/version"))
+            .map_err(|e| Error::Connection(format!("Invalid browser_url {}: {}", browser_url, e)))?;
+        let body = helper::http_get(&version_url, timeout)?;
+        let version: Value = serde_json::from_str(&body).map_err(|e| {
+            Error::Connection(format!("Invalid response from {}: {}", version_url, e))
+        })?;
+
+        version["webSocketDebuggerUrl"]
+            .as_str()
+            .map(String::from)
+            .ok_or_else(|| {
+                Error::Connection(format!("No webSocketDebuggerUrl in {}", version_url))
+            })
+    }
+
+    /// Locates the chrome executable to launch when no
+    /// `LaunchOptions::executable_path` is given
+    ///
+    /// Uses the `PUPPETEER_EXECUTABLE_PATH` environment variable if it is set,
+    /// failing if it does not exist. Otherwise tries, in order, the revision
+    /// named by `PUPPETEER_CHROMIUM_REVISION` (or the default one) in the
+    /// cache directory, the chromium downloaded at build time and finally
+    /// `google-chrome`, `chromium` and `chromium-browser` on the `PATH`. The
+    /// error lists every location that was tried.
+    pub fn executable_path(&self) -> Result<String, Error> {
+        let mut tried = Vec::new();
+
+        if let Some(executable_path) = env::var_os("PUPPETEER_EXECUTABLE_PATH") {
+            // An explicit choice is not second-guessed with another chrome
+            let executable_path = PathBuf::from(executable_path);
+            if executable_path.is_file() {
+                return Ok(executable_path.to_string_lossy().into_owned());
+            }
+            return Err(Error::Launch(format!(
+                "PUPPETEER_EXECUTABLE_PATH={} does not exist",
+                executable_path.display()
+            )));
+        }
+
+        let revision = env::var("PUPPETEER_CHROMIUM_REVISION")
+            .unwrap_or_else(|_| DEFAULT_REVISION.to_string());
+        let executable_path = revision_executable_path(&self.cache_dir(), &revision);
+        if executable_path.is_file() {
+            return Ok(executable_path.to_string_lossy().into_owned());
+        }
+        tried.push(format!("{} (revision {})", executable_path.display(), revision));
+
+        if revision == DEFAULT_REVISION {
+            // The build script renames ARCHIVE_DIR to chrome-<revision>
+            let executable_path = Path::new(env!("OUT_DIR"))
+                .join(format!("chrome-{}", DEFAULT_REVISION))
+                .join(EXECUTABLE);
+            if executable_path.is_file() {
+                return Ok(executable_path.to_string_lossy().into_owned());
+            }
+            tried.push(format!("{} (downloaded at build time)", executable_path.display()));
+        }
+
+        let path = env::var_os("PATH").unwrap_or_default();
+        for name in SYSTEM_EXECUTABLES.iter() {
+            if let Some(executable_path) = find_in_path(name, &path) {
+                return Ok(executable_path.to_string_lossy().into_owned());
+            }
+            tried.push(format!("{} (on the PATH)", name));
         }
 
-        // puppeteer-core doesn't take into account PUPPETEER_* env variables.
-        //if (!this._isPuppeteerCore) {
-        //  const executablePath = process.env['PUPPETEER_EXECUTABLE_PATH'];
-        //  if (executablePath) {
-        //    const missingText = !fs.existsSync(executablePath) ? 'Tried to use PUPPETEER_EXECUTABLE_PATH env variable to launch browser but did not find any executable at: ' + executablePath : null;
-        //    return { executablePath, missingText };
-        //  }
-        //  const revision = process.env['PUPPETEER_CHROMIUM_REVISION'];
-        //  if (revision) {
-        //    const revisionInfo = browserFetcher.revisionInfo(revision);
-        //    const missingText = !revisionInfo.local ? 'Tried to use PUPPETEER_CHROMIUM_REVISION env variable to launch browser but did not find executable at: ' + revisionInfo.executablePath : null;
-        //    return {executablePath: revisionInfo.executablePath, missingText};
-        //  }
-        //}
-
-        chrome_path
-            .join("chrome")
-            .to_str()
-            .expect("Failed to unwrap path to chrome executable")
-            .to_string()
+        Err(Error::Launch(format!(
+            "Could not find a chrome executable, set LaunchOptions::executable_path or \
+             PUPPETEER_EXECUTABLE_PATH. Tried:\n{}",
+            tried.join("\n")
+        )))
     }
 
-    fn initial_arguments() -> Vec<String> {
+    /// The directory revisions of chromium are downloaded to
+    ///
+    /// Either `PUPPETEER_CACHE_DIR`, or `.local-chromium` in the project root
+    /// or the current directory.
+    pub fn cache_dir(&self) -> PathBuf {
+        if let Some(cache_dir) = env::var_os("PUPPETEER_CACHE_DIR") {
+            return PathBuf::from(cache_dir);
+        }
+        match &self.project_root {
+            Some(project_root) => Path::new(project_root).join(".local-chromium"),
+            None => PathBuf::from(".local-chromium"),
+        }
+    }
+
+    /// Returns the arguments chrome is launched with for `options`, except for
+    /// those added at launch such as `--remote-debugging-port`
+    pub fn default_args(options: &LaunchOptions) -> Vec<String> {
         let mut chrome_arguments = Vec::new();
 
         for arg in DEFAULT_ARGS.iter() {
             chrome_arguments.push(arg.to_string());
         }
 
-        return chrome_arguments;
-    }
+        if let Some(user_data_dir) = &options.user_data_dir {
+            chrome_arguments.push(format!("--user-data-dir={}", user_data_dir));
+        }
+        if options.devtools {
+            chrome_arguments.push(String::from("--auto-open-devtools-for-tabs"));
+        }
+        if options.headless && !options.devtools {
+            chrome_arguments.push(String::from("--headless"));
+            chrome_arguments.push(String::from("--hide-scrollbars"));
+            chrome_arguments.push(String::from("--mute-audio"));
+        }
+        // Open a blank tab unless the arguments include a URL to open
+        if options.args.iter().all(|arg| arg.starts_with('-')) {
+            chrome_arguments.push(String::from("about:blank"));
+        }
+        chrome_arguments.extend(options.args.iter().cloned());
 
-    fn wait_for_ws_endpoint(stderr: ChildStderr, timeout: u32) -> String {
-        let stderr = BufReader::new(stderr);
+        chrome_arguments
+    }
 
-        // TODO timeout
+    /// Reads `output` of chrome line by line on a thread of its own until
+    /// chrome closes it, logging each line if `dumpio` is set
+    ///
+    /// The lines are also sent to the returned receiver, for as long as it is
+    /// not dropped.
+    fn drain_output<R: Read + Send + 'static>(
+        name: &'static str,
+        output: R,
+        dumpio: bool,
+    ) -> mpsc::Receiver<String> {
+        let (sender, receiver) = mpsc::channel::<String>();
+        let spawned = thread::Builder::new()
+            .name(format!("chrome_{}", name))
+            .spawn(move || {
+                let mut output = BufReader::new(output);
+                let mut line = Vec::new();
+                loop {
+                    line.clear();
+                    match output.read_until(b'\n', &mut line) {
+                        Ok(0) | Err(_) => return,
+                        Ok(_) => (),
+                    }
+                    let line = String::from_utf8_lossy(&line).trim_end().to_string();
+                    if dumpio {
+                        info!(target: BROWSER_STDIO_TARGET, "[{}] {}", name, line);
+                    }
+                    let _ = sender.send(line);
+                }
+            });
+        if let Err(e) = spawned {
+            warn!("Failed to start a thread draining the {} of chrome: {}", name, e);
+        }
+        receiver
+    }
 
-        for line in stderr.lines() {
-            if let Ok(line) = line {
-                let regex = Regex::new(r"^DevTools listening on (ws://.*)$").unwrap();
-                let captures = regex.captures(&line);
+    /// Reads the DevTools endpoint from the lines chrome printed to stderr
+    ///
+    /// Gives up after `timeout` milliseconds, or never if it is `0`. The
+    /// returned errors include what chrome printed so far, which usually
+    /// explains why it failed to start.
+    fn wait_for_ws_endpoint(
+        receiver: mpsc::Receiver<String>,
+        timeout: u32,
+    ) -> Result<String, Error> {
+        let regex = Regex::new(r"^DevTools listening on (ws://.*)$").unwrap();
+        let deadline = Instant::now() + Duration::from_millis(u64::from(timeout));
+        let mut output = Vec::new();
+
+        loop {
+            let line = if timeout == 0 {
+                receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                let now = Instant::now();
+                if now >= deadline {
+                    Err(RecvTimeoutError::Timeout)
+                } else {
+                    receiver.recv_timeout(deadline - now)
+                }
+            };
 
-                if let Some(captures) = captures {
-                    return String::from(captures.get(1).unwrap().as_str());
+            match line {
+                Ok(line) => {
+                    if let Some(captures) = regex.captures(&line) {
+                        return Ok(String::from(captures.get(1).unwrap().as_str()));
+                    }
+                    output.push(line);
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(Error::Timeout(format!(
+                        "Timed out after {} ms while trying to connect to the browser! Browser output:\n{}",
+                        timeout,
+                        output.join("\n")
+                    )));
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(Error::Launch(format!(
+                        "Failed to launch Chromium! Ensure the chrome sandbox is setup properly. Browser output:\n{}",
+                        output.join("\n")
+                    )));
                 }
             }
         }
+    }
 
-        panic!("Failed to launch Chromium! Ensure the chrome sandbox is setup properly");
+    /// Waits for the tab chrome opens on startup
+    async fn ensure_initial_page<'a>(browser: &'a Browser, timeout: u32) -> Result<(), Error> {
+        await!(browser.wait_for_target(
+            |target| target.target_type() == &TargetType::Page,
+            timeout
+        ))?;
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::error::Error;
+    use crate::launcher::{find_in_path, IgnoreDefaultArgs, LaunchOptions, Launcher};
+    use std::env;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Cursor, Write};
+    use std::net::TcpListener;
+    use std::process;
+    use std::sync::mpsc;
+    use std::thread;
+
+    #[test]
+    fn test_default_args() {
+        let _ = env_logger::try_init();
+
+        let mut options = LaunchOptions::new();
+        options.args = vec!["--window-size=800,600".to_string()];
+        let args = Launcher::default_args(&options);
+        assert!(args.contains(&"--headless".to_string()));
+        assert!(args.contains(&"--disable-extensions".to_string()));
+        assert_eq!(&args[args.len() - 2..], ["about:blank", "--window-size=800,600"]);
+
+        options.devtools = true;
+        options.user_data_dir = Some("/tmp/profile".to_string());
+        let args = Launcher::default_args(&options);
+        assert!(!args.contains(&"--headless".to_string()));
+        assert!(args.contains(&"--auto-open-devtools-for-tabs".to_string()));
+        assert!(args.contains(&"--user-data-dir=/tmp/profile".to_string()));
+
+        // Only consulted by launch, so default_args is unaffected
+        options.ignore_default_args = IgnoreDefaultArgs::All;
+        assert!(Launcher::default_args(&options).contains(&"--disable-extensions".to_string()));
+    }
+
+    #[test]
+    fn test_find_in_path() {
+        let _ = env_logger::try_init();
+
+        // Unique, so that concurrent runs of the tests don't collide
+        let pid = process::id();
+        let empty_dir = env::temp_dir().join(format!("rusty_puppet_find_in_path_empty-{}", pid));
+        let bin_dir = env::temp_dir().join(format!("rusty_puppet_find_in_path_bin-{}", pid));
+        fs::create_dir_all(&empty_dir).unwrap();
+        fs::create_dir_all(&bin_dir).unwrap();
+        fs::write(bin_dir.join("chromium"), "").unwrap();
+
+        let path = env::join_paths(&[&empty_dir, &bin_dir]).unwrap();
+        assert_eq!(find_in_path("chromium", &path), Some(bin_dir.join("chromium")));
+        assert_eq!(find_in_path("google-chrome", &path), None);
 
-    /*
-    async fn ensure_initial_page(browser: Browser) {
-      // Wait for initial page target to be created.
-      if (browser.targets().find(target => target.type() === "page"))
-        return;
-
-      let initialPageCallback;
-      const initialPagePromise = new Promise(resolve => initialPageCallback = resolve);
-      const listeners = [helper.addEventListener(browser, "targetcreated", target => {
-        if (target.type() === "page")
-          initialPageCallback();
-      })];
-
-      await initialPagePromise;
-      helper.removeEventListeners(listeners);
+        fs::remove_dir_all(&empty_dir).unwrap();
+        fs::remove_dir_all(&bin_dir).unwrap();
+    }
+
+    #[test]
+    fn test_drain_output() {
+        let _ = env_logger::try_init();
+
+        let output = Cursor::new(b"first\r\nsecond \xff\nDevTools listening on ws://x".to_vec());
+        let lines: Vec<String> = Launcher::drain_output("stderr", output, true)
+            .iter()
+            .collect();
+        assert_eq!(lines, ["first", "second \u{fffd}", "DevTools listening on ws://x"]);
+    }
+
+    #[test]
+    fn test_wait_for_ws_endpoint() {
+        let _ = env_logger::try_init();
+
+        let (sender, receiver) = mpsc::channel();
+        sender.send("starting".to_string()).unwrap();
+        sender.send("DevTools listening on ws://127.0.0.1:9222/x".to_string()).unwrap();
+        let endpoint = Launcher::wait_for_ws_endpoint(receiver, 1000).unwrap();
+        assert_eq!(endpoint, "ws://127.0.0.1:9222/x");
+
+        // Chrome keeps running without printing the endpoint
+        let (sender, receiver) = mpsc::channel();
+        sender.send("still starting".to_string()).unwrap();
+        match Launcher::wait_for_ws_endpoint(receiver, 100) {
+            Err(Error::Timeout(message)) => assert!(message.contains("still starting")),
+            other => panic!("Expected a timeout, got {:?}", other),
+        }
+        drop(sender);
+
+        // Chrome exits without printing the endpoint
+        let (sender, receiver) = mpsc::channel();
+        sender.send("crashed".to_string()).unwrap();
+        drop(sender);
+        match Launcher::wait_for_ws_endpoint(receiver, 1000) {
+            Err(Error::Launch(message)) => assert!(message.contains("crashed")),
+            other => panic!("Expected a launch error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_ws_endpoint() {
+        let _ = env_logger::try_init();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let browser_url = format!("http://{}", listener.local_addr().unwrap());
+
+        // Plays the part of the DevTools HTTP handler
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&mut stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            assert!(request_line.starts_with("GET /json/version "));
+
+            let body = "{\"webSocketDebuggerUrl\":\"ws://127.0.0.1:9222/devtools/browser/abc\"}";
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+            .unwrap();
+        });
+
+        assert_eq!(
+            Launcher::resolve_ws_endpoint(&browser_url, 1000).unwrap(),
+            "ws://127.0.0.1:9222/devtools/browser/abc",
+        );
+        server.join().unwrap();
     }
-    */
 }
@@ -1,20 +1,53 @@
 #![feature(futures_api, async_await, await_macro)]
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate futures;
+#[cfg(unix)]
+extern crate libc;
 extern crate rand;
 extern crate regex;
+#[cfg(feature = "fetcher")]
+extern crate reqwest;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
 extern crate serde_json;
+#[cfg(feature = "fetcher")]
+extern crate sha2;
 extern crate websocket;
+#[cfg(feature = "fetcher")]
+extern crate zip;
 
 pub mod browser;
+pub mod browser_context;
+#[path = "../build/chromium.rs"]
+#[cfg_attr(not(feature = "fetcher"), allow(dead_code))]
+mod chromium;
 pub mod connection;
+pub mod error;
+#[cfg(feature = "fetcher")]
+pub mod fetcher;
+/// The generator of the protocol bindings, included for its tests
+#[cfg(test)]
+#[path = "../build/protocol.rs"]
+#[allow(dead_code)]
+mod generator;
 pub mod handle;
+mod helper;
+pub mod js_handle;
 pub mod launcher;
 pub mod message;
 pub mod page;
+pub mod pipe;
+pub mod protocol;
+pub mod session;
+#[cfg(unix)]
+mod signals;
+pub mod target;
+pub mod transport;
 pub mod ws;
+
+pub use crate::error::Error;
@@ -1,54 +1,60 @@
-use std::collections::HashMap;
-use std::fmt;
-
-#[derive(Debug)]
-pub enum Method {
-    Browser_getVersion,
-    Page_navigate,
-}
-
-impl fmt::Display for Method {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let string = format!("{:?}", self).replace("_", ".");
-        write!(f, "{}", string)
-    }
-}
+use crate::protocol::Command;
+use serde_json::value::Value;
 
 #[derive(Debug)]
 pub struct Message {
-    method: Method,
-    params: Option<HashMap<String, String>>,
+    method: String,
+    params: Option<Value>,
+    session_id: Option<String>,
 }
 
 impl Message {
-    pub fn new(method: Method) -> Message {
+    /// Builds a message with arbitrary parameters, for methods that have no
+    /// typed command, e.g. experimental ones of a newer Chrome
+    pub fn raw(method: &str, params: Value) -> Message {
         Message {
-            method: method,
-            params: None,
+            method: method.to_string(),
+            params: Some(params),
+            session_id: None,
         }
     }
 
-    pub fn new_with_params(method: Method, params: HashMap<String, String>) -> Message {
+    /// Builds the message for a typed command
+    pub fn from_command<C: Command>(command: &C) -> Message {
+        let params = serde_json::to_value(command).expect("Commands serialize to JSON objects");
         Message {
-            method: method,
+            method: C::NAME.to_string(),
             params: Some(params),
+            session_id: None,
         }
     }
 
+    pub fn method(&self) -> &str {
+        &self.method
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.session_id.as_ref().map(String::as_str)
+    }
+
+    /// Routes the message to the target attached with the given session id
+    pub fn with_session_id(mut self, session_id: String) -> Message {
+        self.session_id = Some(session_id);
+        self
+    }
+
     /// Adds the provided ID to the dataset and serializes the message as JSON
     pub fn serialize(&self, id: u64) -> String {
-        let data = if let Some(params) = &self.params {
-            json!({
-                "id": id,
-                "method": format!("{}", self.method),
-                "params": self.params
-            })
-        } else {
-            json!({
-                "id": id,
-                "method": format!("{}", self.method),
-            })
-        };
+        let mut data = json!({
+            "id": id,
+            "method": &self.method,
+        });
+        if let Some(params) = &self.params {
+            data["params"] = params.clone();
+        }
+        if let Some(session_id) = &self.session_id {
+            data["sessionId"] = json!(session_id);
+        }
 
         format!("{}", data)
     }
@@ -58,35 +64,60 @@ impl Message {
 mod test {
     extern crate env_logger;
 
-    use crate::message::{Message, Method};
+    use crate::message::Message;
+    use crate::protocol::{browser, page};
 
     #[test]
-    fn test_mesage_display() {
+    fn test_message_without_params() {
         let _ = env_logger::try_init();
 
-        let message = Message {
-            method: Method::Browser_getVersion,
-            params: None,
-        };
+        let message = Message::from_command(&browser::GetVersion::new());
 
         assert_eq!(
             message.serialize(123),
-            "{\"id\":123,\"method\":\"Browser.getVersion\"}",
+            "{\"id\":123,\"method\":\"Browser.getVersion\",\"params\":{}}",
+        );
+    }
+
+    #[test]
+    fn test_message_raw() {
+        let _ = env_logger::try_init();
+
+        let message = Message::raw(
+            "Input.dispatchMouseEvent",
+            json!({ "type": "mousePressed", "x": 10.5, "y": 20, "clickCount": 1 }),
+        );
+
+        assert_eq!(
+            message.serialize(3),
+            "{\"id\":3,\"method\":\"Input.dispatchMouseEvent\",\"params\":{\"clickCount\":1,\"type\":\"mousePressed\",\"x\":10.5,\"y\":20}}",
+        );
+    }
+
+    #[test]
+    fn test_message_from_command() {
+        let _ = env_logger::try_init();
+
+        let mut navigate = page::Navigate::new("https://example.com".to_string());
+        navigate.referrer = Some("https://example.org".to_string());
+        let message = Message::from_command(&navigate);
+
+        assert_eq!(
+            message.serialize(42),
+            "{\"id\":42,\"method\":\"Page.navigate\",\"params\":{\"referrer\":\"https://example.org\",\"url\":\"https://example.com\"}}",
         );
+    }
+
+    #[test]
+    fn test_message_session_id() {
+        let _ = env_logger::try_init();
 
-        let message = Message {
-            method: Method::Page_navigate,
-            params: Some(
-                [("url".to_string(), "https://example.com".to_string())]
-                    .iter()
-                    .cloned()
-                    .collect(),
-            ),
-        };
+        let message =
+            Message::from_command(&page::Enable::new()).with_session_id("ABC".to_string());
 
         assert_eq!(
-            message.serialize(456),
-            "{\"id\":456,\"method\":\"Page.navigate\",\"params\":{\"url\":\"https://example.com\"}}",
+            message.serialize(7),
+            "{\"id\":7,\"method\":\"Page.enable\",\"params\":{},\"sessionId\":\"ABC\"}",
         );
     }
 }
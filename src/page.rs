@@ -1,20 +1,355 @@
-// TODO Use response from some crate
-pub struct Response {}
+use crate::error::Error;
+use crate::helper;
+use crate::js_handle::{self, JsHandle};
+use crate::launcher::Viewport;
+use crate::protocol::runtime::{self, CallArgument, RemoteObject};
+use crate::protocol::{emulation, network, page, security};
+use crate::session::CdpSession;
+use crate::target::Target;
+use crate::transport::Event;
+use futures::StreamExt;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::value::Value;
+use std::collections::HashMap;
 
-pub struct PageOptions {}
+/// The HTTP response of the main document loaded by a navigation
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub url: String,
+    pub status: u16,
+    pub status_text: String,
+    pub headers: HashMap<String, String>,
+}
+
+impl Response {
+    fn from_value(response: &Value) -> Response {
+        let headers = response["headers"]
+            .as_object()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.as_str().unwrap_or("").to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Response {
+            url: response["url"].as_str().unwrap_or("").to_string(),
+            status: response["status"].as_u64().unwrap_or(0) as u16,
+            status_text: response["statusText"].as_str().unwrap_or("").to_string(),
+            headers: headers,
+        }
+    }
+
+    /// Whether the status is in the 2xx range
+    pub fn ok(&self) -> bool {
+        self.status >= 200 && self.status <= 299
+    }
+}
 
-pub struct Page {}
+/// When to consider a navigation finished
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaitUntil {
+    /// The `load` event has fired
+    Load,
+    /// The `DOMContentLoaded` event has fired
+    DomContentLoaded,
+    /// There have been no network connections for at least 500 ms
+    NetworkIdle0,
+    /// There have been no more than 2 network connections for at least 500 ms
+    NetworkIdle2,
+}
+
+impl WaitUntil {
+    /// The name of the `Page.lifecycleEvent` that satisfies the condition
+    fn lifecycle_event(&self) -> &'static str {
+        match self {
+            WaitUntil::Load => "load",
+            WaitUntil::DomContentLoaded => "DOMContentLoaded",
+            WaitUntil::NetworkIdle0 => "networkIdle",
+            WaitUntil::NetworkIdle2 => "networkAlmostIdle",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PageOptions {
+    pub wait_until: WaitUntil,
+    /// Maximum navigation time in milliseconds, `0` disables the timeout
+    pub timeout: u32,
+}
+
+impl PageOptions {
+    pub fn new() -> PageOptions {
+        PageOptions {
+            wait_until: WaitUntil::Load,
+            timeout: 30000,
+        }
+    }
+}
+
+/// A single tab, driven through the session attached to its target
+///
+/// Clones share the session.
+#[derive(Clone)]
+pub struct Page {
+    target: Target,
+    session: CdpSession,
+}
 
 impl Page {
-    pub fn new() -> Page {
-        Page {}
+    pub fn new(target: Target, session: CdpSession) -> Page {
+        Page {
+            target: target,
+            session: session,
+        }
+    }
+
+    /// Enables the domains the page depends on
+    pub async fn initialize(&self, ignore_https_errors: bool) -> Result<(), Error> {
+        await!(self.session.send(page::Enable::new()))?;
+        await!(self.session.send(page::SetLifecycleEventsEnabled::new(true)))?;
+        await!(self.session.send(network::Enable::new()))?;
+        if ignore_https_errors {
+            await!(self.session.send(security::SetIgnoreCertificateErrors::new(true)))?;
+        }
+        Ok(())
+    }
+
+    pub async fn set_viewport<'a>(&'a self, viewport: &'a Viewport) -> Result<(), Error> {
+        let screen_orientation = if viewport.isLandscape {
+            emulation::ScreenOrientation {
+                r#type: emulation::ScreenOrientationType::LandscapePrimary,
+                angle: 90,
+            }
+        } else {
+            emulation::ScreenOrientation {
+                r#type: emulation::ScreenOrientationType::PortraitPrimary,
+                angle: 0,
+            }
+        };
+        let mut device_metrics = emulation::SetDeviceMetricsOverride::new(
+            i64::from(viewport.width),
+            i64::from(viewport.height),
+            f64::from(viewport.deviceScaleFactor),
+            viewport.isMobile,
+        );
+        device_metrics.screen_orientation = Some(screen_orientation);
+        await!(self.session.send(device_metrics))?;
+        await!(self.session.send(emulation::SetTouchEmulationEnabled::new(viewport.hasTouch)))?;
+        Ok(())
+    }
+
+    pub fn target(&self) -> &Target {
+        &self.target
+    }
+
+    pub fn target_id(&self) -> &str {
+        self.target.target_id()
+    }
+
+    pub fn session_id(&self) -> &str {
+        self.session.session_id()
+    }
+
+    pub(crate) fn session(&self) -> &CdpSession {
+        &self.session
+    }
+
+    /// Attaches a new session to the page's target, e.g. to send commands the
+    /// page does not expose without interfering with its own session
+    pub async fn create_cdp_session(&self) -> Result<CdpSession, Error> {
+        await!(self.target.create_cdp_session())
+    }
+
+    /// Evaluates JavaScript in the page and deserializes the result
+    ///
+    /// `expression_or_function` is either an expression, such as
+    /// `document.title`, or a function, such as `(a, b) => a + b`, which is
+    /// called with `args`. Promises are awaited, and exceptions or rejections
+    /// fail with `Error::JavaScript`. Passing `args` to an expression fails
+    /// with `Error::Unsupported`.
+    pub async fn evaluate<'a, T: DeserializeOwned>(
+        &'a self,
+        expression_or_function: &'a str,
+        args: Vec<Value>,
+    ) -> Result<T, Error> {
+        let result = await!(self.call(expression_or_function, args, true))?;
+        js_handle::from_remote_object(&result)
     }
 
-    pub fn goto(&self, url: String) -> Response {
-        let options = PageOptions {};
-        self.goto_with_options(url, options)
+    /// Like `evaluate`, but leaves the result in the page, e.g. for DOM nodes
+    /// and other values that cannot be serialized
+    pub async fn evaluate_handle<'a>(
+        &'a self,
+        expression_or_function: &'a str,
+        args: Vec<Value>,
+    ) -> Result<JsHandle, Error> {
+        let result = await!(self.call(expression_or_function, args, false))?;
+        Ok(JsHandle::new(self.session.clone(), result))
     }
-    pub fn goto_with_options(&self, url: String, options: PageOptions) -> Response {
-        unimplemented!();
+
+    async fn call<'a>(
+        &'a self,
+        expression_or_function: &'a str,
+        args: Vec<Value>,
+        return_by_value: bool,
+    ) -> Result<RemoteObject, Error> {
+        if !is_function(expression_or_function) {
+            if !args.is_empty() {
+                return Err(Error::Unsupported(format!(
+                    "{} is not a function, so it takes no arguments",
+                    expression_or_function
+                )));
+            }
+            let mut evaluate = runtime::Evaluate::new(expression_or_function.to_string());
+            evaluate.return_by_value = Some(return_by_value);
+            evaluate.await_promise = Some(true);
+            let result = await!(self.session.send(evaluate))?;
+            js_handle::check_exception(result.exception_details)?;
+            return Ok(result.result);
+        }
+
+        // `callFunctionOn` needs an object or an execution context to call the
+        // function in. We don't track the execution contexts of the page, which
+        // change with every navigation, so we call it on a handle to the global
+        // object of the current one, at the cost of two more round trips.
+        let global = await!(self.session.send(runtime::Evaluate::new("globalThis".to_string())))?;
+        let global = JsHandle::new(self.session.clone(), global.result);
+        let mut call_function_on = runtime::CallFunctionOn::new(expression_or_function.to_string());
+        call_function_on.object_id = global.remote_object().object_id.clone();
+        call_function_on.arguments = Some(
+            args.into_iter()
+                .map(|arg| CallArgument {
+                    value: Some(arg),
+                    unserializable_value: None,
+                    object_id: None,
+                })
+                .collect(),
+        );
+        call_function_on.return_by_value = Some(return_by_value);
+        call_function_on.await_promise = Some(true);
+        let result = await!(self.session.send(call_function_on));
+        // The call failing matters more than the global object leaking
+        let _ = await!(global.dispose());
+        let result = result?;
+        js_handle::check_exception(result.exception_details)?;
+        Ok(result.result)
+    }
+
+    pub async fn goto(&self, url: String) -> Result<Option<Response>, Error> {
+        let options = PageOptions::new();
+        await!(self.goto_with_options(url, options))
+    }
+
+    /// Navigates to `url` and waits for `options.wait_until`
+    ///
+    /// Resolves to the response of the main document, or `None` when the
+    /// navigation did not load a new document, e.g. for `about:blank` or a
+    /// change of the URL fragment.
+    pub async fn goto_with_options(
+        &self,
+        url: String,
+        options: PageOptions,
+    ) -> Result<Option<Response>, Error> {
+        // Subscribe before navigating so that no event can be missed
+        let mut events = self
+            .session
+            .subscribe(&["Page.lifecycleEvent", "Network.responseReceived"]);
+        let lifecycle_event = options.wait_until.lifecycle_event();
+
+        // Chrome only replies to `Page.navigate` once the navigation commits,
+        // so the timeout covers the reply as well as the lifecycle events
+        let url = &url;
+        let navigation = async move {
+            let result = await!(self.session.send(page::Navigate::new(url.clone())))?;
+            if let Some(error_text) = result.error_text {
+                return Err(Error::Navigation(format!("{} at {}", error_text, url)));
+            }
+
+            let frame_id = result.frame_id;
+            let loader_id = match result.loader_id {
+                Some(loader_id) => loader_id,
+                // Same-document navigations have no loader and no response
+                None => return Ok(None),
+            };
+
+            let mut response = None;
+            loop {
+                let event = match await!(events.next()) {
+                    Some(Event::Protocol(event)) => event,
+                    Some(Event::Connected) => continue,
+                    Some(Event::Disconnected) => {
+                        return Err(Error::Connection(format!(
+                            "The connection closed while navigating to {}",
+                            url
+                        )));
+                    }
+                    // The session's events end when its target is detached
+                    None => return Err(Error::TargetClosed),
+                };
+                let params = &event.params;
+                if params["loaderId"].as_str() != Some(loader_id.as_str()) {
+                    continue;
+                }
+
+                match event.method.as_str() {
+                    "Network.responseReceived" => {
+                        if params["type"] == "Document" && params["frameId"] == frame_id.as_str() {
+                            response = Some(Response::from_value(&params["response"]));
+                        }
+                    }
+                    "Page.lifecycleEvent" => {
+                        if params["frameId"] == frame_id.as_str()
+                            && params["name"] == lifecycle_event
+                        {
+                            return Ok(response);
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        };
+
+        match await!(helper::timeout(navigation, options.timeout))? {
+            Some(result) => result,
+            None => Err(Error::Timeout(format!(
+                "Navigation to {} exceeded {} ms",
+                url, options.timeout
+            ))),
+        }
+    }
+}
+
+/// Whether JavaScript source is a function rather than an expression, going by
+/// how it starts, e.g. `function`, `async (a) =>` or `x =>`
+fn is_function(source: &str) -> bool {
+    lazy_static! {
+        static ref FUNCTION: Regex =
+            Regex::new(r"^(async\s+)?(function\b|(\([^)]*\)|[\w$]+)\s*=>)").unwrap();
+    }
+    FUNCTION.is_match(source.trim_start())
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::page::is_function;
+
+    #[test]
+    fn test_is_function() {
+        let _ = env_logger::try_init();
+
+        assert!(is_function("function() { return 1; }"));
+        assert!(is_function("  async function named(a) { await a; }"));
+        assert!(is_function("(a, b) => a + b"));
+        assert!(is_function("async () => document.title"));
+        assert!(is_function("x => x * 2"));
+        assert!(!is_function("document.title"));
+        assert!(!is_function("functionName()"));
+        assert!(!is_function("(1 + 2)"));
+        assert!(!is_function("[1, 2].map(x => x * 2)"));
     }
 }
@@ -0,0 +1,199 @@
+//! Contains the pipe implementation for communicating with Chrome
+//!
+//! When launched with `--remote-debugging-pipe`, Chrome reads messages from
+//! file descriptor 3 and writes to file descriptor 4, with every frame
+//! terminated by a null byte. No debugging port is opened.
+
+use crate::error::Error;
+use crate::transport::{Dispatcher, Transport};
+use futures::channel::mpsc;
+use futures::executor::block_on_stream;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::Command;
+use std::thread;
+
+/// The transport that communicates with Chrome over a pair of pipes
+///
+/// Like the `WebSocketTransport`, it fires up one thread writing frames and
+/// one thread reading frames and handing them to the `Dispatcher`.
+pub struct PipeTransport {
+    sender: mpsc::UnboundedSender<String>,
+}
+
+impl PipeTransport {
+    pub fn new<W, R>(writer: W, reader: R, dispatcher: Dispatcher) -> Result<PipeTransport, Error>
+    where
+        W: Write + Send + 'static,
+        R: Read + Send + 'static,
+    {
+        let (message_sender, message_receiver) = mpsc::unbounded::<String>();
+
+        thread::Builder::new()
+            .name("pipe_sender".to_string())
+            .spawn(PipeTransport::build_sender(writer, message_receiver))?;
+        thread::Builder::new()
+            .name("pipe_receiver".to_string())
+            .spawn(PipeTransport::build_receiver(reader, dispatcher))?;
+
+        Ok(PipeTransport {
+            sender: message_sender,
+        })
+    }
+
+    fn build_sender<W: Write>(
+        mut writer: W,
+        receiver: mpsc::UnboundedReceiver<String>,
+    ) -> impl FnOnce() {
+        move || {
+            debug!("Spawned pipe sender thread");
+
+            // Ends once the transport is closed or every handle is dropped,
+            // which closes the pipe and lets Chrome know we are done
+            for message in block_on_stream(receiver) {
+                let written = writer
+                    .write_all(message.as_bytes())
+                    .and_then(|_| writer.write_all(b"\0"))
+                    .and_then(|_| writer.flush());
+                if let Err(e) = written {
+                    error!("Send Loop: {:?}", e);
+                    return;
+                }
+            }
+
+            debug!("Exiting pipe sender thread");
+        }
+    }
+
+    fn build_receiver<R: Read>(reader: R, mut dispatcher: Dispatcher) -> impl FnOnce() {
+        move || {
+            debug!("Spawned pipe reciever thread");
+            let mut reader = BufReader::new(reader);
+
+            loop {
+                let mut frame = Vec::new();
+                match reader.read_until(b'\0', &mut frame) {
+                    Ok(0) => break,
+                    Ok(_) => (),
+                    Err(e) => {
+                        error!("Receive Loop: {:?}", e);
+                        break;
+                    }
+                }
+
+                if frame.pop() != Some(b'\0') {
+                    warn!("Pipe closed in the middle of a frame");
+                    break;
+                }
+                match String::from_utf8(frame) {
+                    Ok(message) => dispatcher.dispatch(&message),
+                    Err(e) => warn!("Received a frame that is not UTF-8: {}", e),
+                }
+            }
+
+            debug!("Exiting pipe reciever thread");
+        }
+    }
+}
+
+impl Transport for PipeTransport {
+    fn send(&self, message: String) -> Result<(), Error> {
+        self.sender
+            .unbounded_send(message)
+            .map_err(|_| Error::Connection("The pipe is closed".to_string()))
+    }
+
+    fn close(&self) {
+        self.sender.close_channel();
+    }
+}
+
+/// Hands one end of two socket pairs to the process spawned by `command` as
+/// file descriptors 3 and 4, and returns the other ends as `(writer, reader)`
+///
+/// The child's ends are owned by `command`, so drop it after spawning to
+/// notice when Chrome exits.
+#[cfg(unix)]
+pub fn attach(
+    command: &mut Command,
+) -> Result<(std::os::unix::net::UnixStream, std::os::unix::net::UnixStream), Error> {
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::net::UnixStream;
+    use std::os::unix::process::CommandExt;
+
+    let (writer, child_reader) = UnixStream::pair()?;
+    let (reader, child_writer) = UnixStream::pair()?;
+
+    unsafe {
+        command.pre_exec(move || {
+            // Copy both above 4 first, so that neither dup2 can overwrite the
+            // other when one of them already is 3 or 4
+            let read_fd = libc::fcntl(child_reader.as_raw_fd(), libc::F_DUPFD, 5);
+            let write_fd = libc::fcntl(child_writer.as_raw_fd(), libc::F_DUPFD, 5);
+            if read_fd < 0
+                || write_fd < 0
+                || libc::dup2(read_fd, 3) < 0
+                || libc::dup2(write_fd, 4) < 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    Ok((writer, reader))
+}
+
+#[cfg(not(unix))]
+pub fn attach(_command: &mut Command) -> Result<(std::fs::File, std::fs::File), Error> {
+    Err(Error::Launch(
+        "--remote-debugging-pipe is only supported on unix".to_string(),
+    ))
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    extern crate env_logger;
+
+    use crate::connection::Connection;
+    use crate::protocol::browser;
+    use futures::executor::block_on;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+    use std::thread;
+
+    #[test]
+    fn test_pipe_roundtrip() {
+        let _ = env_logger::try_init();
+
+        let (writer, mut chrome_reader) = UnixStream::pair().unwrap();
+        let (reader, mut chrome_writer) = UnixStream::pair().unwrap();
+        let connection = Connection::from_pipe(writer, reader, 0).unwrap();
+
+        // Plays the part of Chrome, replying to the first frame it reads
+        let chrome = thread::spawn(move || {
+            let mut frame = Vec::new();
+            BufReader::new(&mut chrome_reader)
+                .read_until(b'\0', &mut frame)
+                .unwrap();
+            assert_eq!(
+                frame,
+                b"{\"id\":1,\"method\":\"Browser.getVersion\",\"params\":{}}\0".to_vec()
+            );
+
+            chrome_writer
+                .write_all(b"{\"method\":\"Target.targetCreated\",\"params\":{}}\0")
+                .unwrap();
+            chrome_writer
+                .write_all(
+                    b"{\"id\":1,\"result\":{\"protocolVersion\":\"1.3\",\"product\":\"Chrome\",\
+                      \"revision\":\"@609904\",\"userAgent\":\"Mozilla/5.0\",\"jsVersion\":\"7.1\"}}\0",
+                )
+                .unwrap();
+        });
+
+        let version = block_on(connection.send(browser::GetVersion::new())).unwrap();
+        assert_eq!(version.product, "Chrome");
+        chrome.join().unwrap();
+    }
+}
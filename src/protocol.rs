@@ -0,0 +1,129 @@
+//! Typed bindings for the DevTools protocol
+//!
+//! Generated by `build.rs` from the protocol description in `protocol/`, with
+//! a module per domain. Every command is a struct implementing `Command`,
+//! which knows the method name and the type Chrome replies with, and every
+//! event is a struct implementing `Event`.
+//!
+//! The description is that of ChromeDevTools/devtools-protocol at commit
+//! `bb9208f5db271fc9f10978a11082d1ec57d52df2`, which is years newer than the
+//! default revision, Chromium 72 (r609904). Newer fields are optional in
+//! replies or only sent on request, and unknown fields and enum values are
+//! ignored, so the replies of older versions still parse. The tests below
+//! check the replies of that version for every command the crate sends.
+//!
+//! ```ignore
+//! let result = await!(connection.send(target::CreateTarget::new("about:blank".to_string())))?;
+//! println!("Created {}", result.target_id);
+//! ```
+
+#![allow(deprecated, non_camel_case_types)]
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A command that can be sent to Chrome
+pub trait Command: Serialize {
+    /// The method name, e.g. `Page.navigate`
+    const NAME: &'static str;
+    /// The `result` of Chrome's reply
+    type Returns: DeserializeOwned;
+}
+
+/// The parameters of an event emitted by Chrome
+pub trait Event: DeserializeOwned {
+    /// The method name, e.g. `Page.lifecycleEvent`
+    const NAME: &'static str;
+}
+
+/// The `result` of commands that do not return anything
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Empty {}
+
+include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::protocol::runtime::RemoteObjectType;
+    use crate::protocol::{browser, page, runtime, target, Command, Empty};
+    use serde_json::value::Value;
+
+    fn parse<C: Command>(result: Value) -> C::Returns {
+        serde_json::from_value(result).unwrap()
+    }
+
+    /// Replies as sent by Chromium 72, the default revision
+    #[test]
+    fn test_replies_of_default_revision() {
+        let _ = env_logger::try_init();
+
+        let version = parse::<browser::GetVersion>(json!({
+            "protocolVersion": "1.3",
+            "product": "HeadlessChrome/72.0.3617.0",
+            "revision": "@a6ad7c8ae9d4e2c5d4b3b0d0e8e3c8a1a6f1b6b5",
+            "userAgent": "Mozilla/5.0 HeadlessChrome/72.0.3617.0",
+            "jsVersion": "7.2.233",
+        }));
+        assert_eq!(version.product, "HeadlessChrome/72.0.3617.0");
+
+        let navigate = parse::<page::Navigate>(json!({ "frameId": "F1", "loaderId": "L1" }));
+        assert_eq!(navigate.loader_id, Some("L1".to_string()));
+        let navigate = parse::<page::Navigate>(json!({ "frameId": "F1" }));
+        assert_eq!(navigate.loader_id, None);
+        let navigate = parse::<page::Navigate>(json!({
+            "frameId": "F1",
+            "loaderId": "L2",
+            "errorText": "net::ERR_NAME_NOT_RESOLVED",
+        }));
+        assert!(navigate.error_text.is_some());
+
+        let evaluate = parse::<runtime::Evaluate>(json!({
+            "result": { "type": "number", "value": 3, "description": "3" },
+        }));
+        assert_eq!(evaluate.result.r#type, RemoteObjectType::Number);
+        let exception = json!({
+            "result": {
+                "type": "object",
+                "subtype": "error",
+                "className": "Error",
+                "description": "Error: boom\n    at <anonymous>:1:7",
+                "objectId": "{\"injectedScriptId\":1,\"id\":1}",
+            },
+            "exceptionDetails": {
+                "exceptionId": 1,
+                "text": "Uncaught",
+                "lineNumber": 0,
+                "columnNumber": 6,
+                "scriptId": "12",
+                "stackTrace": {
+                    "callFrames": [{
+                        "functionName": "",
+                        "scriptId": "12",
+                        "url": "",
+                        "lineNumber": 0,
+                        "columnNumber": 6,
+                    }],
+                },
+                "exception": { "type": "object", "subtype": "error", "className": "Error" },
+            },
+        });
+        let call_function_on = parse::<runtime::CallFunctionOn>(exception.clone());
+        assert!(call_function_on.exception_details.is_some());
+        parse::<runtime::Evaluate>(exception);
+
+        let attach = parse::<target::AttachToTarget>(json!({ "sessionId": "S1" }));
+        assert_eq!(attach.session_id, "S1");
+        let create = parse::<target::CreateTarget>(json!({ "targetId": "T1" }));
+        assert_eq!(create.target_id, "T1");
+        let context = parse::<target::CreateBrowserContext>(json!({ "browserContextId": "C1" }));
+        assert_eq!(context.browser_context_id, "C1");
+        let contexts = parse::<target::GetBrowserContexts>(json!({ "browserContextIds": [] }));
+        assert!(contexts.browser_context_ids.is_empty());
+
+        // The commands that reply with nothing
+        let empty: Empty = serde_json::from_value(json!({})).unwrap();
+        assert_eq!(empty, Empty {});
+    }
+}
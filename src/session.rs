@@ -0,0 +1,58 @@
+use crate::connection::Connection;
+use crate::error::Error;
+use crate::message::Message;
+use crate::protocol::{target, Command};
+use crate::transport::EventStream;
+use serde_json::value::Value;
+
+/// A protocol session attached to a single target
+///
+/// Every message sent through the session is tagged with its `sessionId`, so
+/// that Chrome routes it to the target rather than the browser. When the
+/// target is destroyed or the session detached, pending calls fail with
+/// `Error::TargetClosed` and event streams end.
+#[derive(Clone)]
+pub struct CdpSession {
+    connection: Connection,
+    session_id: String,
+}
+
+impl CdpSession {
+    pub fn new(connection: Connection, session_id: String) -> CdpSession {
+        CdpSession {
+            connection: connection,
+            session_id: session_id,
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Sends a command to the target and resolves to Chrome's reply
+    pub async fn send<C: Command>(&self, command: C) -> Result<C::Returns, Error> {
+        let result = await!(self.send_raw(Message::from_command(&command)))?;
+        Ok(serde_json::from_value(result)?)
+    }
+
+    /// Sends a message to the target and resolves to the untyped `result` of
+    /// Chrome's reply
+    pub async fn send_raw(&self, message: Message) -> Result<Value, Error> {
+        let message = message.with_session_id(self.session_id.clone());
+        await!(self.connection.send_raw(message))
+    }
+
+    /// Returns a stream of the events with any of the given method names that
+    /// were emitted by the target
+    pub fn subscribe(&self, methods: &[&str]) -> EventStream {
+        self.connection.subscribe_session(&self.session_id, methods)
+    }
+
+    /// Detaches the session from its target, which keeps running
+    pub async fn detach(&self) -> Result<(), Error> {
+        let mut detach_from_target = target::DetachFromTarget::new();
+        detach_from_target.session_id = Some(self.session_id.clone());
+        await!(self.connection.send(detach_from_target))?;
+        Ok(())
+    }
+}
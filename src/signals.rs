@@ -0,0 +1,208 @@
+//! Tears down launched browsers when the process is signalled
+//!
+//! A signal handler may only do async-signal-safe work, so it just writes
+//! the signal number to a pipe. A thread reading the other end kills the
+//! browsers registered for that signal, removes their temporary profiles and
+//! then raises the signal again with the action that was installed before
+//! ours, e.g. the host application's own handler or the default one.
+
+use crate::error::Error;
+use libc::c_int;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::mem;
+use std::os::unix::io::FromRawFd;
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+lazy_static! {
+    /// The browsers to tear down, by pid
+    static ref BROWSERS: Mutex<HashMap<u32, Registration>> = Mutex::new(HashMap::new());
+    /// The actions our handler replaced, by signal
+    static ref HANDLED_SIGNALS: Mutex<HashMap<c_int, libc::sigaction>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The file descriptor the signal handler writes to
+static SIGNAL_PIPE: AtomicUsize = AtomicUsize::new(0);
+
+/// A browser to tear down on any of `signals`
+struct Registration {
+    temporary_user_data_dir: Option<PathBuf>,
+    signals: Vec<c_int>,
+}
+
+/// Tears down the browser with `pid` when any of `signals` is received
+pub fn register(
+    pid: u32,
+    temporary_user_data_dir: Option<PathBuf>,
+    signals: &[c_int],
+) -> Result<(), Error> {
+    if signals.is_empty() {
+        return Ok(());
+    }
+
+    install(signals)?;
+    let registration = Registration {
+        temporary_user_data_dir: temporary_user_data_dir,
+        signals: signals.to_vec(),
+    };
+    BROWSERS.lock().unwrap().insert(pid, registration);
+    Ok(())
+}
+
+/// Forgets the browser with `pid`, e.g. because it has been closed
+pub fn unregister(pid: u32) {
+    BROWSERS.lock().unwrap().remove(&pid);
+}
+
+fn install(signals: &[c_int]) -> Result<(), Error> {
+    let mut handled_signals = HANDLED_SIGNALS.lock().unwrap();
+
+    if handled_signals.is_empty() {
+        let mut fds = [0 as c_int; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let reader = unsafe { File::from_raw_fd(fds[0]) };
+        SIGNAL_PIPE.store(fds[1] as usize, Ordering::SeqCst);
+
+        thread::Builder::new()
+            .name("signal_watcher".to_string())
+            .spawn(move || watch(reader))?;
+    }
+
+    for &signal in signals {
+        if let Entry::Vacant(entry) = handled_signals.entry(signal) {
+            entry.insert(set_action(signal, &handler_action())?);
+        }
+    }
+
+    Ok(())
+}
+
+/// The action that runs `handle_signal`
+fn handler_action() -> libc::sigaction {
+    let mut action: libc::sigaction = unsafe { mem::zeroed() };
+    action.sa_sigaction = handle_signal as extern "C" fn(c_int) as libc::sighandler_t;
+    // Like `signal` does, so that we interrupt no more system calls than the
+    // handlers we replace
+    action.sa_flags = libc::SA_RESTART;
+    unsafe {
+        libc::sigemptyset(&mut action.sa_mask);
+    }
+    action
+}
+
+/// Installs `action` for `signal` and returns the one it replaced
+fn set_action(signal: c_int, action: &libc::sigaction) -> Result<libc::sigaction, Error> {
+    let mut previous_action: libc::sigaction = unsafe { mem::zeroed() };
+    if unsafe { libc::sigaction(signal, action, &mut previous_action) } != 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(previous_action)
+}
+
+extern "C" fn handle_signal(signal: c_int) {
+    let byte = signal as u8;
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::SeqCst) as c_int,
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        );
+    }
+}
+
+fn watch(mut reader: File) {
+    let mut byte = [0u8; 1];
+
+    while reader.read_exact(&mut byte).is_ok() {
+        let signal = c_int::from(byte[0]);
+        info!("Received signal {}, closing launched browsers", signal);
+
+        // Browsers launched without handling this signal are left alone
+        let browsers: Vec<_> = {
+            let mut browsers = BROWSERS.lock().unwrap();
+            let pids: Vec<u32> = browsers
+                .iter()
+                .filter(|(_, registration)| registration.signals.contains(&signal))
+                .map(|(&pid, _)| pid)
+                .collect();
+            pids.into_iter()
+                .filter_map(|pid| browsers.remove(&pid).map(|registration| (pid, registration)))
+                .collect()
+        };
+        for (pid, registration) in browsers {
+            unsafe {
+                libc::kill(pid as libc::pid_t, libc::SIGKILL);
+                libc::waitpid(pid as libc::pid_t, ptr::null_mut(), 0);
+            }
+            if let Some(user_data_dir) = registration.temporary_user_data_dir {
+                let _ = fs::remove_dir_all(user_data_dir);
+            }
+        }
+
+        // Let the signal do what it would have done without us. `raise`
+        // delivers it to this thread before returning, so the previous action
+        // has run by the time ours is back in place, unless it ended the
+        // process.
+        let handled_signals = HANDLED_SIGNALS.lock().unwrap();
+        if let Some(previous_action) = handled_signals.get(&signal) {
+            if let Err(e) = set_action(signal, previous_action) {
+                warn!("Failed to restore the action of signal {}: {}", signal, e);
+                continue;
+            }
+            unsafe {
+                libc::raise(signal);
+            }
+            if let Err(e) = set_action(signal, &handler_action()) {
+                warn!("Failed to handle signal {} again: {}", signal, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::signals::{self, BROWSERS};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_signal_tears_down_its_browsers_only() {
+        let _ = env_logger::try_init();
+
+        // Ignored before we handle them, so that raising them is harmless.
+        // Nothing runs with these pids, which are above the largest pid_max.
+        unsafe {
+            libc::signal(libc::SIGUSR1, libc::SIG_IGN);
+            libc::signal(libc::SIGUSR2, libc::SIG_IGN);
+        }
+        signals::register(2_000_000_001, None, &[libc::SIGUSR1]).unwrap();
+        signals::register(2_000_000_002, None, &[libc::SIGUSR2]).unwrap();
+
+        unsafe {
+            libc::raise(libc::SIGUSR2);
+        }
+        for _ in 0..100 {
+            if !BROWSERS.lock().unwrap().contains_key(&2_000_000_002) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        {
+            let browsers = BROWSERS.lock().unwrap();
+            assert!(!browsers.contains_key(&2_000_000_002));
+            assert!(browsers.contains_key(&2_000_000_001));
+        }
+        signals::unregister(2_000_000_001);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::connection::Connection;
+use crate::error::Error;
+use crate::protocol;
+use crate::session::CdpSession;
+use crate::transport::Event;
+use serde_json::value::Value;
+use std::fmt;
+
+/// The kind of a target
+#[derive(Debug, Clone, PartialEq)]
+pub enum TargetType {
+    Page,
+    BackgroundPage,
+    ServiceWorker,
+    SharedWorker,
+    Browser,
+    Other(String),
+}
+
+impl TargetType {
+    fn from_str(target_type: &str) -> TargetType {
+        match target_type {
+            "page" => TargetType::Page,
+            "background_page" => TargetType::BackgroundPage,
+            "service_worker" => TargetType::ServiceWorker,
+            "shared_worker" => TargetType::SharedWorker,
+            "browser" => TargetType::Browser,
+            other => TargetType::Other(other.to_string()),
+        }
+    }
+}
+
+/// A change to the targets of the browser
+#[derive(Debug, Clone)]
+pub enum TargetEvent {
+    Created(Target),
+    /// The URL or another property of the target changed
+    Changed(Target),
+    /// The target with this id was closed
+    Destroyed(String),
+}
+
+impl TargetEvent {
+    /// The events a `TargetEvent` is built from
+    pub const METHODS: [&'static str; 3] = [
+        "Target.targetCreated",
+        "Target.targetInfoChanged",
+        "Target.targetDestroyed",
+    ];
+
+    /// Parses an event emitted to the browser, returning `None` for other
+    /// events and those emitted to a session
+    pub fn from_event(connection: &Connection, event: Event) -> Option<TargetEvent> {
+        let event = match event {
+            Event::Protocol(event) => event,
+            _ => return None,
+        };
+        if event.session_id.is_some() {
+            return None;
+        }
+
+        let params = &event.params;
+        let target = || Target::from_info(connection.clone(), &params["targetInfo"]);
+        match event.method.as_str() {
+            "Target.targetCreated" => target().map(TargetEvent::Created),
+            "Target.targetInfoChanged" => target().map(TargetEvent::Changed),
+            "Target.targetDestroyed" => params["targetId"]
+                .as_str()
+                .map(|target_id| TargetEvent::Destroyed(target_id.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// A target of the browser, such as a tab or a service worker
+///
+/// This is a snapshot of the target as it was when it was last reported by
+/// the browser, e.g. its `url` is not updated as the target navigates.
+#[derive(Clone)]
+pub struct Target {
+    connection: Connection,
+    target_id: String,
+    target_type: TargetType,
+    url: String,
+    opener_id: Option<String>,
+    browser_context_id: Option<String>,
+}
+
+impl Target {
+    /// Builds a target from the `TargetInfo` reported by the browser
+    pub fn from_info(connection: Connection, info: &Value) -> Option<Target> {
+        Some(Target {
+            connection: connection,
+            target_id: info["targetId"].as_str()?.to_string(),
+            target_type: TargetType::from_str(info["type"].as_str().unwrap_or("other")),
+            url: info["url"].as_str().unwrap_or("").to_string(),
+            opener_id: info["openerId"].as_str().map(String::from),
+            browser_context_id: info["browserContextId"].as_str().map(String::from),
+        })
+    }
+
+    pub fn target_id(&self) -> &str {
+        &self.target_id
+    }
+
+    pub fn target_type(&self) -> &TargetType {
+        &self.target_type
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// The id of the target that opened this one, e.g. with `window.open`
+    pub fn opener_id(&self) -> Option<&str> {
+        self.opener_id.as_ref().map(String::as_str)
+    }
+
+    pub fn browser_context_id(&self) -> Option<&str> {
+        self.browser_context_id.as_ref().map(String::as_str)
+    }
+
+    /// Attaches a new session to the target
+    ///
+    /// Sessions are independent of each other, so the events enabled in one
+    /// are not emitted to another.
+    pub async fn create_cdp_session(&self) -> Result<CdpSession, Error> {
+        let mut attach_to_target = protocol::target::AttachToTarget::new(self.target_id.clone());
+        attach_to_target.flatten = Some(true);
+        let result = await!(self.connection.send(attach_to_target))?;
+
+        Ok(CdpSession::new(self.connection.clone(), result.session_id))
+    }
+}
+
+impl fmt::Debug for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Target")
+            .field("target_id", &self.target_id)
+            .field("target_type", &self.target_type)
+            .field("url", &self.url)
+            .field("opener_id", &self.opener_id)
+            .field("browser_context_id", &self.browser_context_id)
+            .finish()
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    extern crate env_logger;
+
+    use crate::connection::Connection;
+    use crate::target::{TargetEvent, TargetType};
+    use crate::transport::{Event, ProtocolEvent};
+    use std::os::unix::net::UnixStream;
+
+    #[test]
+    fn test_target_event_from_event() {
+        let _ = env_logger::try_init();
+
+        let (writer, _) = UnixStream::pair().unwrap();
+        let (reader, _) = UnixStream::pair().unwrap();
+        let connection = Connection::from_pipe(writer, reader, 0).unwrap();
+        let event = |method: &str, session_id: Option<&str>| {
+            Event::Protocol(ProtocolEvent {
+                method: method.to_string(),
+                params: json!({
+                    "targetInfo": {
+                        "targetId": "ABC",
+                        "type": "service_worker",
+                        "url": "https://example.com/sw.js",
+                        "browserContextId": "DEF",
+                    }
+                }),
+                session_id: session_id.map(String::from),
+            })
+        };
+
+        match TargetEvent::from_event(&connection, event("Target.targetCreated", None)) {
+            Some(TargetEvent::Created(target)) => {
+                assert_eq!(target.target_id(), "ABC");
+                assert_eq!(target.target_type(), &TargetType::ServiceWorker);
+                assert_eq!(target.url(), "https://example.com/sw.js");
+                assert_eq!(target.opener_id(), None);
+                assert_eq!(target.browser_context_id(), Some("DEF"));
+            }
+            other => panic!("Expected a created target, got {:?}", other),
+        }
+        let from_session = event("Target.targetCreated", Some("S"));
+        assert!(TargetEvent::from_event(&connection, from_session).is_none());
+        let other = event("Page.lifecycleEvent", None);
+        assert!(TargetEvent::from_event(&connection, other).is_none());
+    }
+}
@@ -0,0 +1,357 @@
+//! Contains what the WebSocket and pipe transports have in common
+//!
+//! A transport only moves serialized frames to and from Chrome. Replies and
+//! events are routed by a `Dispatcher` running on the thread that reads from
+//! the transport, while the `Connection` registers callbacks and subscribers
+//! with it through a `Registration`.
+
+use crate::error::Error;
+use crate::protocol;
+use futures::channel::{mpsc, oneshot};
+use serde_json::value::Value;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone)]
+pub enum Event {
+    Connected,
+    /// An event emitted by Chrome, such as `Page.lifecycleEvent`
+    Protocol(ProtocolEvent),
+    /// The transport closed, so no more events will follow
+    Disconnected,
+}
+
+/// A frame sent by Chrome without an `id`, i.e. not in reply to a message
+#[derive(Debug, Clone)]
+pub struct ProtocolEvent {
+    pub method: String,
+    pub params: Value,
+    /// The session of the target that emitted the event, if not the browser
+    pub session_id: Option<String>,
+}
+
+impl ProtocolEvent {
+    /// Parses an event frame, returning `None` if the frame has no method
+    pub fn from_frame(mut data: Value) -> Option<ProtocolEvent> {
+        let method = data["method"].as_str()?.to_string();
+        let session_id = data["sessionId"].as_str().map(String::from);
+
+        Some(ProtocolEvent {
+            method: method,
+            params: data["params"].take(),
+            session_id: session_id,
+        })
+    }
+
+    /// Parses the parameters as the typed event `E`, returning `None` if this
+    /// is a different event
+    pub fn parse<E: protocol::Event>(&self) -> Option<Result<E, Error>> {
+        if self.method != E::NAME {
+            return None;
+        }
+        Some(serde_json::from_value(self.params.clone()).map_err(Error::from))
+    }
+}
+
+/// A stream of the events matching a subscription
+pub type EventStream = mpsc::UnboundedReceiver<Event>;
+
+/// A repesentation of a response from Chrome after a `send()`
+pub enum Response {
+    Frame(Value),
+    Meta(Event),
+    /// The session the message was sent to detached before Chrome replied
+    TargetClosed,
+}
+
+/// The sending half of a connection to Chrome
+pub trait Transport: Send + Sync {
+    /// Queues a serialized message for sending
+    fn send(&self, message: String) -> Result<(), Error>;
+
+    /// Closes the transport, after which no more messages are sent
+    fn close(&self);
+}
+
+/// A registration for events with any of `methods`, optionally restricted to
+/// the events of a single session
+struct Subscriber {
+    methods: Vec<String>,
+    session_id: Option<String>,
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+impl Subscriber {
+    fn matches(&self, event: &ProtocolEvent) -> bool {
+        self.methods.contains(&event.method)
+            && (self.session_id.is_none() || self.session_id == event.session_id)
+    }
+}
+
+/// Creates a linked `Registration` and `Dispatcher`
+pub fn dispatcher() -> (Registration, Dispatcher) {
+    let (register_callback, receive_callback) = mpsc::unbounded::<(u64, Callback)>();
+    let (register_subscriber, receive_subscriber) = mpsc::unbounded::<Subscriber>();
+
+    let registration = Registration {
+        callback_registration: register_callback,
+        subscriber_registration: register_subscriber,
+    };
+    let dispatcher = Dispatcher {
+        receive_callback: receive_callback,
+        receive_subscriber: receive_subscriber,
+        callbacks: HashMap::new(),
+        subscribers: Vec::new(),
+    };
+
+    (registration, dispatcher)
+}
+
+/// Resolves to the reply to a message, which was sent to `session_id`
+struct Callback {
+    session_id: Option<String>,
+    sender: oneshot::Sender<Response>,
+}
+
+/// Registers callbacks and subscribers with a `Dispatcher`
+#[derive(Clone)]
+pub struct Registration {
+    callback_registration: mpsc::UnboundedSender<(u64, Callback)>,
+    subscriber_registration: mpsc::UnboundedSender<Subscriber>,
+}
+
+impl Registration {
+    /// Returns a future resolving to the reply to the message with `id`,
+    /// sent to the given session or to the browser if `session_id` is `None`
+    ///
+    /// Must be called before the message is sent.
+    pub fn register_callback(
+        &self,
+        id: u64,
+        session_id: Option<String>,
+    ) -> oneshot::Receiver<Response> {
+        let (sender, receiver) = oneshot::channel::<Response>();
+        let _ = self.callback_registration.unbounded_send((
+            id,
+            Callback {
+                session_id: session_id,
+                sender: sender,
+            },
+        ));
+        receiver
+    }
+
+    /// Returns a stream of the events with any of the given method names,
+    /// emitted by the given session or by any session if `session_id` is `None`
+    ///
+    /// Events received before this call are not replayed, so subscribe before
+    /// sending the message that triggers them. Dropping the stream ends the
+    /// subscription. Every subscriber, even one without methods, receives
+    /// `Event::Disconnected` when the transport closes.
+    pub fn subscribe(&self, methods: &[&str], session_id: Option<String>) -> EventStream {
+        let (sender, receiver) = mpsc::unbounded::<Event>();
+        let _ = self.subscriber_registration.unbounded_send(Subscriber {
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            session_id: session_id,
+            sender: sender,
+        });
+        receiver
+    }
+}
+
+/// Routes the frames read from a transport to their callback or subscribers
+///
+/// Dropping the dispatcher fails every pending callback and ends every event
+/// stream after a final `Event::Disconnected`, so it should live exactly as
+/// long as the transport is readable.
+/// Likewise, when a session detaches, the callbacks and subscribers of that
+/// session are failed and ended.
+pub struct Dispatcher {
+    receive_callback: mpsc::UnboundedReceiver<(u64, Callback)>,
+    receive_subscriber: mpsc::UnboundedReceiver<Subscriber>,
+    callbacks: HashMap<u64, Callback>,
+    subscribers: Vec<Subscriber>,
+}
+
+impl Dispatcher {
+    pub fn dispatch(&mut self, message: &str) {
+        let data: Value = match serde_json::from_str(message) {
+            Ok(data) => data,
+            Err(err) => {
+                warn!("Failed to parse frame {}", err);
+                return;
+            }
+        };
+
+        // Callbacks and subscribers are registered before their message is
+        // sent, so every one relevant to this frame is already queued
+        while let Ok(Some((id, callback))) = self.receive_callback.try_next() {
+            self.callbacks.insert(id, callback);
+        }
+        while let Ok(Some(subscriber)) = self.receive_subscriber.try_next() {
+            self.subscribers.push(subscriber);
+        }
+        // Drop subscribers whose stream has been dropped, including those that
+        // match no event, such as the ones of `Browser::disconnected`
+        self.subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+
+        if let Some(id) = data["id"].as_u64() {
+            if let Some(callback) = self.callbacks.remove(&id) {
+                let _ = callback.sender.send(Response::Frame(data));
+            } else {
+                error!("No callback registered for id {}. Race condition?", id);
+            }
+        } else if let Some(event) = ProtocolEvent::from_frame(data.clone()) {
+            debug!("Dispatching event {}", event.method);
+            // Also drop subscribers whose stream has been dropped since
+            self.subscribers.retain(|subscriber| {
+                !subscriber.matches(&event)
+                    || subscriber
+                        .sender
+                        .unbounded_send(Event::Protocol(event.clone()))
+                        .is_ok()
+            });
+
+            if event.method == "Target.detachedFromTarget" {
+                if let Some(session_id) = event.params["sessionId"].as_str() {
+                    self.detach(session_id);
+                }
+            }
+        } else {
+            warn!("Received a frame with neither id nor method: {}", data);
+        }
+    }
+
+    /// Fails the pending callbacks and ends the event streams of a session
+    fn detach(&mut self, session_id: &str) {
+        debug!("Session {} detached", session_id);
+        let detached: Vec<u64> = self
+            .callbacks
+            .iter()
+            .filter(|(_, callback)| {
+                callback.session_id.as_ref().map(String::as_str) == Some(session_id)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in detached {
+            if let Some(callback) = self.callbacks.remove(&id) {
+                let _ = callback.sender.send(Response::TargetClosed);
+            }
+        }
+
+        self.subscribers.retain(|subscriber| {
+            subscriber.session_id.as_ref().map(String::as_str) != Some(session_id)
+        });
+    }
+}
+
+impl Drop for Dispatcher {
+    fn drop(&mut self) {
+        while let Ok(Some(subscriber)) = self.receive_subscriber.try_next() {
+            self.subscribers.push(subscriber);
+        }
+        for subscriber in &self.subscribers {
+            let _ = subscriber.sender.unbounded_send(Event::Disconnected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate env_logger;
+
+    use crate::transport::{self, Event, ProtocolEvent, Response, Subscriber};
+    use futures::channel::mpsc;
+    use futures::executor::{block_on, block_on_stream};
+
+    #[test]
+    fn test_event_from_frame() {
+        let _ = env_logger::try_init();
+
+        let event = ProtocolEvent::from_frame(json!({
+            "method": "Page.lifecycleEvent",
+            "params": { "name": "load" },
+            "sessionId": "ABC",
+        }))
+        .unwrap();
+        assert_eq!(event.method, "Page.lifecycleEvent");
+        assert_eq!(event.params["name"], "load");
+        assert_eq!(event.session_id, Some("ABC".to_string()));
+
+        assert!(ProtocolEvent::from_frame(json!({ "id": 1, "result": {} })).is_none());
+    }
+
+    #[test]
+    fn test_subscriber_matches() {
+        let _ = env_logger::try_init();
+
+        let (sender, _receiver) = mpsc::unbounded();
+        let subscriber = Subscriber {
+            methods: vec!["Page.lifecycleEvent".to_string()],
+            session_id: Some("ABC".to_string()),
+            sender: sender,
+        };
+
+        let event = |method: &str, session_id: Option<&str>| ProtocolEvent {
+            method: method.to_string(),
+            params: json!({}),
+            session_id: session_id.map(String::from),
+        };
+        assert!(subscriber.matches(&event("Page.lifecycleEvent", Some("ABC"))));
+        assert!(!subscriber.matches(&event("Page.lifecycleEvent", Some("DEF"))));
+        assert!(!subscriber.matches(&event("Page.lifecycleEvent", None)));
+        assert!(!subscriber.matches(&event("Network.responseReceived", Some("ABC"))));
+    }
+
+    #[test]
+    fn test_dispatcher_detach() {
+        let _ = env_logger::try_init();
+
+        let (registration, mut dispatcher) = transport::dispatcher();
+        let session_reply = registration.register_callback(1, Some("ABC".to_string()));
+        let browser_reply = registration.register_callback(2, None);
+        let events = registration.subscribe(&["Page.lifecycleEvent"], Some("ABC".to_string()));
+
+        dispatcher.dispatch(
+            "{\"method\":\"Target.detachedFromTarget\",\"params\":{\"sessionId\":\"ABC\"}}",
+        );
+        match block_on(session_reply) {
+            Ok(Response::TargetClosed) => (),
+            _ => panic!("Expected the pending call to fail with TargetClosed"),
+        }
+        assert_eq!(block_on_stream(events).count(), 0);
+
+        dispatcher.dispatch("{\"id\":2,\"result\":{}}");
+        match block_on(browser_reply) {
+            Ok(Response::Frame(data)) => assert_eq!(data["id"], 2),
+            _ => panic!("Expected the reply to the browser"),
+        }
+    }
+
+    #[test]
+    fn test_dispatcher_drops_closed_subscribers() {
+        let _ = env_logger::try_init();
+
+        let (registration, mut dispatcher) = transport::dispatcher();
+        let events = registration.subscribe(&[], None);
+        let _other_events = registration.subscribe(&["Page.lifecycleEvent"], None);
+        drop(events);
+
+        dispatcher.dispatch("{\"id\":1,\"result\":{}}");
+        assert_eq!(dispatcher.subscribers.len(), 1);
+    }
+
+    #[test]
+    fn test_dispatcher_drop() {
+        let _ = env_logger::try_init();
+
+        let (registration, dispatcher) = transport::dispatcher();
+        let events = registration.subscribe(&[], None);
+        drop(dispatcher);
+
+        let events: Vec<Event> = block_on_stream(events).collect();
+        match events.as_slice() {
+            [Event::Disconnected] => (),
+            _ => panic!("Expected only Disconnected, got {:?}", events),
+        }
+    }
+}